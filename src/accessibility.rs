@@ -0,0 +1,82 @@
+use crate::{
+    LegendConfig,
+    common::{Layer, get_layer_label},
+    entries::layer_entries,
+    error::LegendError,
+};
+use image::DynamicImage;
+use serde_json::Value;
+use svg::node::Text;
+use svg::node::element::{Description, Title};
+use svg::{Document, Node};
+
+/// Marks a legend document as an image for assistive technologies, with a `<title>` and
+/// a `<desc>` as its first children.
+pub fn with_description(mut doc: Document, title: &str, description: &str) -> Document {
+    doc.assign("role", "img");
+    if let Some(children) = doc.get_children_mut() {
+        children.insert(0, Box::new(Description::new().add(Text::new(description))));
+        children.insert(0, Box::new(Title::new(title)));
+    }
+    doc
+}
+
+/// Summarizes the legend of a layer: its label and type, followed by the labels of its
+/// entries, e.g. `Land use (fill layer): park, forest, Other`.
+///
+/// `entries` are the rows drawn when they are not all of the layer's [`layer_entries`].
+pub fn describe_layer(
+    layer: &Layer,
+    config: &LegendConfig,
+    sprites: &[(DynamicImage, Value)],
+    entries: Option<&[(String, String)]>,
+) -> Result<String, LegendError> {
+    let label = get_layer_label(layer, config)?;
+    let mut description = format!("{} ({} layer)", label, layer.layer_type);
+    let entries = match entries {
+        Some(entries) => Some(entries.to_vec()),
+        None => layer_entries(layer, config, sprites)?,
+    };
+    if let Some(entries) = entries {
+        let labels: Vec<&str> = entries.iter().map(|(label, _)| label.as_str()).collect();
+        description.push_str(": ");
+        description.push_str(&labels.join(", "));
+    }
+    Ok(description)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn layer(value: serde_json::Value) -> Layer {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_with_description_goes_first() {
+        let doc = Document::new().add(svg::node::element::Rectangle::new());
+        let svg = with_description(doc, "Water", "Water (fill layer)").to_string();
+        assert!(svg.contains(r#"role="img""#));
+        let title = svg.find("<title>").unwrap();
+        let desc = svg.find("<desc>").unwrap();
+        assert!(title < desc && desc < svg.find("<rect").unwrap());
+        assert!(svg.contains("Water (fill layer)"));
+    }
+
+    #[test]
+    fn test_describe_layer_lists_entries() {
+        let landuse = layer(json!({
+            "id": "landuse", "type": "fill",
+            "paint": {"fill-color": ["match", ["get", "class"], "park", "#00ff00", "#cccccc"]},
+            "metadata": {"legend": {"label": "Land use", "default": "Other"}}
+        }));
+        let description = describe_layer(&landuse, &LegendConfig::default(), &[], None).unwrap();
+        assert_eq!(description, "Land use (fill layer): park, Other");
+
+        let background = layer(json!({"id": "bg", "type": "background"}));
+        let description = describe_layer(&background, &LegendConfig::default(), &[], None).unwrap();
+        assert_eq!(description, "bg (background layer)");
+    }
+}
@@ -0,0 +1,191 @@
+use crate::{
+    LegendConfig,
+    columns::Block,
+    common::{Layer, Source, legend_str, text_element},
+    error::LegendError,
+    layout::{line_height, text_width, wrap_text},
+    locale::remove_localized,
+};
+use std::collections::BTreeMap;
+use svg::Document;
+use svg::node::Text;
+
+/// Returns the `metadata.legend.note` of a layer, localized like its label.
+pub fn layer_note<'a>(
+    layer: &'a Layer,
+    config: &LegendConfig,
+) -> Result<Option<&'a str>, LegendError> {
+    legend_str(layer, config, "note")
+}
+
+/// Returns a copy of the layer without its `metadata.legend.note` (in any locale), for the
+/// pieces of a split layer other than the last.
+pub fn without_note(mut layer: Layer) -> Layer {
+    if let Some(legend) = layer
+        .metadata
+        .as_mut()
+        .and_then(|m| m.get_mut("legend"))
+        .and_then(|l| l.as_object_mut())
+    {
+        legend.remove("note");
+        remove_localized(legend, "note");
+    }
+    layer
+}
+
+/// Draws `note` in italics below a rendered layer legend, wrapped to the legend width (at
+/// least `default_width`), and returns the grown legend.
+pub fn with_note(
+    (doc, width, height): (Document, u32, u32),
+    note: &str,
+    config: &LegendConfig,
+) -> (Document, u32, u32) {
+    let theme = &config.theme;
+    let max_width = width
+        .max(config.default_width)
+        .saturating_sub(2 * theme.padding);
+    let lines = wrap_text(theme, note, false, max_width);
+    let content = lines
+        .iter()
+        .map(|line| theme.padding + text_width(theme, line, false) + theme.padding)
+        .fold(width, u32::max);
+    let mut doc = doc;
+    // The note takes the place of the bottom margin, which moves below it.
+    let mut baseline = (height + theme.font_size).saturating_sub(theme.padding);
+    for line in &lines {
+        let text = text_element(theme, theme.padding, baseline, false)
+            .set("font-style", "italic")
+            .add(Text::new(line.as_str()));
+        doc = doc.add(text);
+        baseline += line_height(theme);
+    }
+    let height = height + lines.len() as u32 * line_height(theme);
+    (
+        doc.set("width", content).set("height", height),
+        content,
+        height,
+    )
+}
+
+/// Renders the attributions of the style's sources as a footer wrapped to `width`.
+/// Attributions are reduced to plain text and repeated ones are listed once; `None` when
+/// no source has one.
+pub fn attribution_block(
+    sources: &BTreeMap<String, Source>,
+    width: u32,
+    config: &LegendConfig,
+) -> Option<Block> {
+    let theme = &config.theme;
+    let mut attributions: Vec<String> = Vec::new();
+    for source in sources.values() {
+        let text = plain_text(source.attribution.as_deref().unwrap_or_default());
+        if !text.is_empty() && !attributions.contains(&text) {
+            attributions.push(text);
+        }
+    }
+    if attributions.is_empty() {
+        return None;
+    }
+    let text = attributions.join(" | ");
+    let lines = wrap_text(theme, &text, false, width.saturating_sub(2 * theme.padding));
+    let height = theme.padding + lines.len() as u32 * line_height(theme);
+    let mut doc = Document::new().set("width", width).set("height", height);
+    let mut baseline = theme.padding / 2 + theme.font_size;
+    for line in &lines {
+        doc = doc
+            .add(text_element(theme, theme.padding, baseline, false).add(Text::new(line.as_str())));
+        baseline += line_height(theme);
+    }
+    Some(Block {
+        svg: doc,
+        width,
+        height,
+        label: Some(text),
+    })
+}
+
+/// Reduces an HTML attribution to its text: tags are dropped, common entities decoded
+/// and whitespace collapsed.
+fn plain_text(html: &str) -> String {
+    let mut text = String::new();
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    let text = text
+        .replace("&copy;", "©")
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&");
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn layer_with_note(note: &str) -> Layer {
+        serde_json::from_value(json!({
+            "id": "water", "type": "fill",
+            "metadata": {"legend": {"note": note}}
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_layer_note() {
+        let config = LegendConfig::default();
+        let layer = layer_with_note("Surveyed 2024");
+        assert_eq!(layer_note(&layer, &config).unwrap(), Some("Surveyed 2024"));
+        assert_eq!(layer_note(&without_note(layer), &config).unwrap(), None);
+    }
+
+    #[test]
+    fn test_with_note_wraps_below_legend() {
+        let config = LegendConfig::default();
+        let note = "Depths below 10 m are approximate and not suitable for navigation";
+        let (doc, width, height) = with_note((Document::new(), 200, 50), note, &config);
+        let svg = doc.to_string();
+        let lines = svg.matches("font-style=\"italic\"").count();
+        assert!(lines > 1);
+        assert_eq!(width, 200);
+        assert_eq!(height, 50 + lines as u32 * 17);
+        // The first baseline sits just below the content.
+        assert!(svg.contains(r#"y="54""#));
+    }
+
+    #[test]
+    fn test_attribution_block() {
+        let sources: BTreeMap<String, Source> = serde_json::from_value(json!({
+            "osm": {"type": "vector",
+                    "attribution": "<a href=\"https://www.openstreetmap.org/copyright\">&copy; OpenStreetMap contributors</a>"},
+            "osm-labels": {"type": "vector",
+                           "attribution": "&copy; OpenStreetMap contributors"},
+            "dem": {"type": "raster-dem"},
+        }))
+        .unwrap();
+        let block = attribution_block(&sources, 400, &LegendConfig::default()).unwrap();
+        assert_eq!(block.label.as_deref(), Some("© OpenStreetMap contributors"));
+        assert_eq!(block.height, 27);
+        assert!(
+            block
+                .svg
+                .to_string()
+                .contains("© OpenStreetMap contributors")
+        );
+        assert!(attribution_block(&BTreeMap::new(), 400, &LegendConfig::default()).is_none());
+    }
+
+    #[test]
+    fn test_plain_text() {
+        assert_eq!(plain_text("<b>A</b> &amp;  B"), "A & B");
+    }
+}
@@ -1,44 +1,77 @@
 use crate::{
-    common::{Layer, get_fill_and_opacity, render_label},
+    LegendConfig,
+    common::{
+        FALLBACK_COLOR, Layer, constant_color, get_fill_and_opacity, has_sprite_icon,
+        parse_expression, render_label, sprite_pattern, svg_id,
+    },
     error::LegendError,
+    layout::inline_width,
 };
+use image::DynamicImage;
+use serde_json::Value;
 use svg::Document;
-use svg::node::element::Rectangle;
+use svg::node::element::{Definitions, Rectangle};
 
 /// Renders a `background` layer legend as an SVG.
 ///
 /// Shows a single rectangle filled with the background color and opacity.
-/// Defaults to `#f0f0f0` if `background-color` is absent.
+/// Defaults to `#f0f0f0` if `background-color` is absent. Constant color expressions
+/// (`rgb`, `to-color`, ...) are evaluated, and a zoom curve is drawn with the color of its
+/// first stop. When `background-pattern`
+/// names an icon of the sprite, the image is tiled into the rectangle instead.
 ///
-/// Returns `(svg_string, width, height)`.
+/// Returns `(document, width, height)`.
 pub fn render_background(
     layer: &Layer,
-    paint: &serde_json::Map<String, serde_json::Value>,
-    default_width: u32,
-    default_height: u32,
-    has_label: bool,
-) -> Result<(String, u32, u32), LegendError> {
-    let color = paint
-        .get("background-color")
-        .and_then(|v| v.as_str())
-        .unwrap_or("#f0f0f0");
+    paint: &serde_json::Map<String, Value>,
+    config: &LegendConfig,
+    sprite_data: &[(DynamicImage, Value)],
+) -> Result<(Document, u32, u32), LegendError> {
+    let &LegendConfig {
+        default_height,
+        has_label,
+        ref theme,
+        ..
+    } = config;
+    let width = inline_width(layer, config, theme.inline_label_x())?;
+    let color = match paint.get("background-color") {
+        Some(value) => match constant_color(Some(value)) {
+            Some(color) => color,
+            None => parse_expression(layer, config, value)?
+                .into_iter()
+                .next()
+                .map(|(_, color)| color)
+                .unwrap_or_else(|| FALLBACK_COLOR.to_string()),
+        },
+        None => "#f0f0f0".to_string(),
+    };
 
     let opacity = paint
         .get("background-opacity")
         .and_then(|v| v.as_f64())
         .unwrap_or(1.0);
 
-    let (fill_value, effective_opacity) = get_fill_and_opacity(color, opacity);
-
     let mut doc = Document::new()
-        .set("width", default_width)
+        .set("width", width)
         .set("height", default_height);
 
+    let (fill_value, effective_opacity) =
+        match paint.get("background-pattern").and_then(|v| v.as_str()) {
+            Some(icon_name) if has_sprite_icon(sprite_data, icon_name) => {
+                let id = svg_id(layer, "pattern");
+                let pattern =
+                    sprite_pattern(sprite_data, icon_name, &id, theme.padding, theme.padding)?;
+                doc = doc.add(Definitions::new().add(pattern));
+                (format!("url(#{})", id), opacity)
+            }
+            _ => get_fill_and_opacity(&color, opacity),
+        };
+
     let rect = Rectangle::new()
-        .set("x", 10)
-        .set("y", 10)
-        .set("width", 30)
-        .set("height", 20)
+        .set("x", theme.padding)
+        .set("y", theme.padding)
+        .set("width", theme.swatch_width)
+        .set("height", theme.swatch_height)
         .set("fill", fill_value)
         .set("fill-opacity", effective_opacity)
         .set("stroke", "#aaaaaa")
@@ -47,10 +80,10 @@ pub fn render_background(
     doc = doc.add(rect);
 
     if has_label {
-        render_label(layer, &mut doc, None, None, None)?;
+        render_label(layer, config, &mut doc, None, None, None)?;
     }
 
-    Ok((doc.to_string(), default_width, default_height))
+    Ok((doc, width, default_height))
 }
 
 #[cfg(test)]
@@ -59,6 +92,13 @@ mod tests {
     use crate::common::Layer;
     use serde_json::json;
 
+    fn config(has_label: bool) -> LegendConfig {
+        LegendConfig {
+            has_label,
+            ..Default::default()
+        }
+    }
+
     fn make_layer(id: &str) -> Layer {
         serde_json::from_value(json!({"id": id, "type": "background"})).unwrap()
     }
@@ -71,7 +111,8 @@ mod tests {
     fn test_render_background_basic() {
         let layer = make_layer("bg");
         let p = paint(json!({"background-color": "#f0f0f0"}));
-        let (svg, width, height) = render_background(&layer, &p, 200, 40, false).unwrap();
+        let (svg, width, height) = render_background(&layer, &p, &config(false), &[]).unwrap();
+        let svg = svg.to_string();
         assert_eq!(width, 200);
         assert_eq!(height, 40);
         assert!(svg.contains("#f0f0f0"));
@@ -81,10 +122,38 @@ mod tests {
     fn test_render_background_with_opacity() {
         let layer = make_layer("bg");
         let p = paint(json!({"background-color": "#ffffff", "background-opacity": 0.5}));
-        let (svg, _, _) = render_background(&layer, &p, 200, 40, false).unwrap();
+        let (svg, _, _) = render_background(&layer, &p, &config(false), &[]).unwrap();
+        let svg = svg.to_string();
         assert!(svg.contains("0.5"));
     }
 
+    #[test]
+    fn test_render_background_color_expressions() {
+        let layer = make_layer("bg");
+        for (color, expected) in [
+            (json!(["rgb", 0, 128, 255]), "#0080ff"),
+            (json!(["to-color", ["get", "c"], "#ff0000"]), "#ff0000"),
+            (
+                json!([
+                    "interpolate",
+                    ["linear"],
+                    ["zoom"],
+                    5,
+                    "#00ff00",
+                    10,
+                    "#0000ff"
+                ]),
+                "#00ff00",
+            ),
+        ] {
+            let p = paint(json!({"background-color": color}));
+            let (svg, _, _) = render_background(&layer, &p, &config(false), &[]).unwrap();
+            let svg = svg.to_string();
+            assert!(svg.contains(expected), "{}", svg);
+            assert!(!svg.contains("#f0f0f0"));
+        }
+    }
+
     #[test]
     fn test_render_background_with_label() {
         let layer: Layer = serde_json::from_value(json!({
@@ -93,7 +162,33 @@ mod tests {
         }))
         .unwrap();
         let p = paint(json!({"background-color": "#ffffff"}));
-        let (svg, _, _) = render_background(&layer, &p, 200, 40, true).unwrap();
+        let (svg, _, _) = render_background(&layer, &p, &config(true), &[]).unwrap();
+        let svg = svg.to_string();
         assert!(svg.contains("Fondo"));
     }
+
+    #[test]
+    fn test_render_background_pattern() {
+        let layer = make_layer("bg");
+        let p = paint(json!({"background-color": "#ff0000", "background-pattern": "paper"}));
+        let sprites = vec![(
+            DynamicImage::new_rgba8(4, 4),
+            json!({"paper": {"x": 0, "y": 0, "width": 4, "height": 4}}),
+        )];
+        let (svg, _, _) = render_background(&layer, &p, &config(false), &sprites).unwrap();
+        let svg = svg.to_string();
+        assert!(svg.contains("<pattern"));
+        assert!(svg.contains("url(#bg-pattern)"));
+        assert!(!svg.contains("#ff0000"));
+    }
+
+    #[test]
+    fn test_render_background_pattern_without_sprite_uses_color() {
+        let layer = make_layer("bg");
+        let p = paint(json!({"background-color": "#ff0000", "background-pattern": "paper"}));
+        let (svg, _, _) = render_background(&layer, &p, &config(false), &[]).unwrap();
+        let svg = svg.to_string();
+        assert!(!svg.contains("<pattern"));
+        assert!(svg.contains("#ff0000"));
+    }
 }
@@ -1,33 +1,42 @@
 use crate::{
-    common::{
-        FONT_SIZE, ICON_HEIGHT, Layer, PADDING, ROW_HEIGHT, extract_color, get_fill_and_opacity,
-        parse_expression, render_label, render_separator,
-    },
+    LegendConfig,
+    common::{Layer, extract_color, get_fill_and_opacity, parse_expression, render_label},
     error::LegendError,
+    layout::{RowLayout, inline_width},
 };
 use svg::Document;
-use svg::node::element::{Circle, Text as SvgText};
+use svg::node::element::Circle;
 
 /// Renders a `circle` layer legend as an SVG.
 ///
 /// - Single-color paint: one circle centered in the SVG, sized by `circle-radius` (capped at 25 px).
-/// - Expression-based paint: one 10 px radius circle per case, stacked vertically with labels.
+/// - Expression-based paint: one circle per case, as wide as the theme's swatch height,
+///   with its label, laid out by [`RowLayout`].
+/// - `entries`: drawn instead of the `circle-color` cases when given.
 ///
-/// Returns `(svg_string, width, height)`.
+/// Returns `(document, width, height)`.
 pub fn render_circle(
     layer: &Layer,
     paint: &serde_json::Map<String, serde_json::Value>,
-    default_width: u32,
-    default_height: u32,
-    has_label: bool,
-) -> Result<(String, u32, u32), LegendError> {
+    config: &LegendConfig,
+    entries: Option<&[(String, String)]>,
+) -> Result<(Document, u32, u32), LegendError> {
+    let &LegendConfig {
+        default_height,
+        has_label,
+        ref theme,
+        ..
+    } = config;
     let color_expr = paint.get("circle-color").ok_or_else(|| {
         LegendError::InvalidJson(format!(
             "Layer '{}': missing 'circle-color' in paint",
             layer.id
         ))
     })?;
-    let cases = parse_expression(layer, color_expr)?;
+    let cases = match entries {
+        Some(entries) => entries.to_vec(),
+        None => parse_expression(layer, config, color_expr)?,
+    };
     let mut radius = paint
         .get("circle-radius")
         .and_then(|v| v.as_f64())
@@ -39,22 +48,29 @@ pub fn render_circle(
         .get("circle-opacity")
         .and_then(|v| v.as_f64())
         .unwrap_or(1.0);
-    let stroke_color =
-        extract_color(paint.get("circle-stroke-color")).unwrap_or("black".to_string());
+    let stroke_opacity = paint
+        .get("circle-stroke-opacity")
+        .and_then(|v| v.as_f64())
+        .unwrap_or(1.0);
+    let (stroke_color, stroke_opacity) = get_fill_and_opacity(
+        &extract_color(paint.get("circle-stroke-color")).unwrap_or("black".to_string()),
+        stroke_opacity,
+    );
     let stroke_width = paint
         .get("circle-stroke-width")
         .and_then(|v| v.as_f64())
         .unwrap_or(0.0);
-    let mut init_y = PADDING as i32;
+    let rows = RowLayout::new(layer, config, &cases, theme.icon_label_x())?;
+    let mut init_y = theme.padding as i32;
     let dynamic_height = if cases.is_empty() {
         0
     } else {
-        ICON_HEIGHT + cases.len() as u32 * ROW_HEIGHT
+        theme.swatch_height + rows.rows_height
     };
     let height = if !cases.is_empty() {
-        if has_label {
-            init_y += ROW_HEIGHT as i32;
-            dynamic_height + ICON_HEIGHT
+        if rows.title_row {
+            init_y += theme.title_height() as i32;
+            dynamic_height + theme.title_space()
         } else {
             dynamic_height
         }
@@ -63,32 +79,31 @@ pub fn render_circle(
     } else {
         default_height
     };
-    let mut doc = Document::new()
-        .set("width", default_width)
-        .set("height", height);
+    let width = if cases.is_empty() {
+        inline_width(layer, config, theme.inline_label_x())?
+    } else {
+        rows.width
+    };
+    let mut doc = Document::new().set("width", width).set("height", height);
     if !cases.is_empty() {
         if has_label {
-            render_label(layer, &mut doc, Some(10), Some(20), Some(true))?;
-            render_separator(&mut doc, default_width, 0, 10);
+            rows.render_title(layer, config, &mut doc, theme.baseline(init_y))?;
         }
-        for (i, (label, color)) in cases.iter().enumerate() {
-            let y = init_y + i as i32 * ROW_HEIGHT as i32;
+        for (i, (_, color)) in cases.iter().enumerate() {
+            let (dx, y) = rows.position(i, init_y);
             let (fill_value, effective_opacity) = get_fill_and_opacity(color, opacity);
+            let r = theme.swatch_height / 2;
             let circle = Circle::new()
-                .set("cx", 20)
-                .set("cy", y + ICON_HEIGHT as i32 / 2)
-                .set("r", PADDING)
+                .set("cx", theme.padding + r + dx)
+                .set("cy", y + r as i32)
+                .set("r", r)
                 .set("fill", fill_value.as_str())
                 .set("fill-opacity", effective_opacity)
                 .set("stroke", stroke_color.as_str())
+                .set("stroke-opacity", stroke_opacity)
                 .set("stroke-width", stroke_width);
-            let text = SvgText::new("")
-                .set("x", 40)
-                .set("y", y + 15)
-                .set("font-size", FONT_SIZE)
-                .set("fill", "black")
-                .add(svg::node::Text::new(label.clone()));
-            doc = doc.add(circle).add(text);
+            let (x, baseline) = (theme.icon_label_x() + dx, theme.baseline(y));
+            rows.render_entry(theme, &mut doc, i, circle, x, baseline);
         }
     } else {
         let color = extract_color(Some(color_expr))?;
@@ -101,13 +116,15 @@ pub fn render_circle(
             .set("fill", fill_value)
             .set("fill-opacity", effective_opacity)
             .set("stroke", stroke_color)
+            .set("stroke-opacity", stroke_opacity)
             .set("stroke-width", stroke_width);
         doc = doc.add(circle);
         if has_label {
-            render_label(layer, &mut doc, None, Some(cy + 5), None)?;
+            let baseline = cy + theme.text_offset() as u32;
+            render_label(layer, config, &mut doc, None, Some(baseline), None)?;
         }
     }
-    Ok((doc.to_string(), default_width, height))
+    Ok((doc, width, height))
 }
 
 #[cfg(test)]
@@ -116,6 +133,13 @@ mod tests {
     use crate::common::Layer;
     use serde_json::json;
 
+    fn config(has_label: bool) -> LegendConfig {
+        LegendConfig {
+            has_label,
+            ..Default::default()
+        }
+    }
+
     fn make_layer(id: &str) -> Layer {
         serde_json::from_value(json!({"id": id, "type": "circle"})).unwrap()
     }
@@ -129,8 +153,9 @@ mod tests {
         let layer = make_layer("test");
         let p = paint(json!({"circle-color": "#ff0000"}));
         // parse_expression returns 1 entry for plain string → multi-case path:
-        // height = ICON_HEIGHT(20) + 1 * ROW_HEIGHT(30) = 50
-        let (svg, width, height) = render_circle(&layer, &p, 200, 40, false).unwrap();
+        // height = swatch_height(20) + 1 * row_height(30) = 50
+        let (svg, width, height) = render_circle(&layer, &p, &config(false), None).unwrap();
+        let svg = svg.to_string();
         assert_eq!(width, 200);
         assert_eq!(height, 50);
         assert!(svg.contains("#ff0000"));
@@ -142,7 +167,8 @@ mod tests {
         let p = paint(json!({
             "circle-color": ["match", ["get", "t"], "a", "#ff0000", "b", "#00ff00", "#cccccc"]
         }));
-        let (svg, _, _) = render_circle(&layer, &p, 200, 40, false).unwrap();
+        let (svg, _, _) = render_circle(&layer, &p, &config(false), None).unwrap();
+        let svg = svg.to_string();
         assert!(svg.contains("#ff0000"));
         assert!(svg.contains("#00ff00"));
     }
@@ -152,7 +178,17 @@ mod tests {
         let layer = make_layer("test");
         // radius=50 is capped to 25 internally; function must succeed
         let p = paint(json!({"circle-color": "#aaaaaa", "circle-radius": 50}));
-        let (svg, _, _) = render_circle(&layer, &p, 200, 80, false).unwrap();
+        let (svg, _, _) = render_circle(
+            &layer,
+            &p,
+            &LegendConfig {
+                default_height: 80,
+                ..config(false)
+            },
+            None,
+        )
+        .unwrap();
+        let svg = svg.to_string();
         assert!(svg.contains("#aaaaaa"));
     }
 
@@ -164,7 +200,8 @@ mod tests {
             "circle-stroke-color": "#000000",
             "circle-stroke-width": 2
         }));
-        let (svg, _, _) = render_circle(&layer, &p, 200, 40, false).unwrap();
+        let (svg, _, _) = render_circle(&layer, &p, &config(false), None).unwrap();
+        let svg = svg.to_string();
         assert!(svg.contains("#000000"));
     }
 
@@ -172,6 +209,6 @@ mod tests {
     fn test_render_circle_missing_color_returns_err() {
         let layer = make_layer("test");
         let p = paint(json!({}));
-        assert!(render_circle(&layer, &p, 200, 40, false).is_err());
+        assert!(render_circle(&layer, &p, &config(false), None).is_err());
     }
 }
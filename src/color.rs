@@ -0,0 +1,412 @@
+use crate::error::LegendError;
+use std::fmt;
+use std::str::FromStr;
+
+/// A color parsed from any MapLibre-valid CSS color string, normalized to RGBA.
+///
+/// Accepts `#rgb`, `#rgba`, `#rrggbb`, `#rrggbbaa`, `rgb(...)`, `rgba(...)`, `hsl(...)`,
+/// `hsla(...)` (comma- or space-separated, with optional `/ alpha`), the CSS named colors
+/// and `transparent`.
+///
+/// `Display` writes `#rrggbb` for opaque colors and `#rrggbbaa` otherwise, so the output
+/// can be fed back into [`Color::parse`] without losing the alpha channel.
+///
+/// ```rust
+/// use maplibre_legend::Color;
+///
+/// let c = Color::parse("rgba(255, 0, 0, 0.5)").unwrap();
+/// assert_eq!(c.to_hex(), "#ff0000");
+/// assert_eq!(c.a, 0.5);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Color {
+    /// Red channel, `0..=255`.
+    pub r: u8,
+    /// Green channel, `0..=255`.
+    pub g: u8,
+    /// Blue channel, `0..=255`.
+    pub b: u8,
+    /// Alpha channel, `0.0..=1.0`.
+    pub a: f64,
+}
+
+impl Color {
+    /// Creates a color from its channels. `a` is clamped to `0.0..=1.0`.
+    pub fn new(r: u8, g: u8, b: u8, a: f64) -> Self {
+        Self {
+            r,
+            g,
+            b,
+            a: a.clamp(0.0, 1.0),
+        }
+    }
+
+    /// Parses a CSS color string. Returns `None` if the string is not a valid color.
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim().to_ascii_lowercase();
+        if let Some(hex) = s.strip_prefix('#') {
+            return parse_hex(hex);
+        }
+        if let Some((name, rest)) = s.split_once('(') {
+            let args = rest.strip_suffix(')')?;
+            return match name.trim() {
+                "rgb" | "rgba" => parse_rgb(args),
+                "hsl" | "hsla" => parse_hsl(args),
+                _ => None,
+            };
+        }
+        if s == "transparent" {
+            return Some(Self::new(0, 0, 0, 0.0));
+        }
+        NAMED_COLORS
+            .iter()
+            .find(|(name, _)| *name == s)
+            .map(|(_, rgb)| Self::new((rgb >> 16) as u8, (rgb >> 8) as u8, *rgb as u8, 1.0))
+    }
+
+    /// Returns the color as `#rrggbb`, ignoring alpha.
+    pub fn to_hex(&self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+
+    /// Returns `true` if the color is fully opaque.
+    pub fn is_opaque(&self) -> bool {
+        self.a >= 1.0
+    }
+}
+
+impl FromStr for Color {
+    type Err = LegendError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s).ok_or_else(|| LegendError::InvalidColor(s.to_string()))
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_opaque() {
+            write!(f, "{}", self.to_hex())
+        } else {
+            write!(f, "{}{:02x}", self.to_hex(), (self.a * 255.0).round() as u8)
+        }
+    }
+}
+
+fn parse_hex(hex: &str) -> Option<Color> {
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let digits: Vec<u8> = match hex.len() {
+        // Shorthand: each digit is doubled (#f80 → #ff8800).
+        3 | 4 => hex
+            .chars()
+            .map(|c| c.to_digit(16).map(|d| (d * 17) as u8))
+            .collect::<Option<_>>()?,
+        6 | 8 => (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+            .collect::<Option<_>>()?,
+        _ => return None,
+    };
+    let a = digits.get(3).map_or(1.0, |a| *a as f64 / 255.0);
+    Some(Color::new(digits[0], digits[1], digits[2], a))
+}
+
+/// Splits functional notation arguments, accepting both `a, b, c, d` and `a b c / d`.
+fn split_args(args: &str) -> Vec<&str> {
+    args.split([',', ' ', '/'])
+        .map(str::trim)
+        .filter(|a| !a.is_empty())
+        .collect()
+}
+
+/// Parses an alpha argument: a number in `0..=1` or a percentage.
+fn parse_alpha(arg: Option<&&str>) -> Option<f64> {
+    match arg {
+        None => Some(1.0),
+        Some(a) => match a.strip_suffix('%') {
+            Some(p) => p.parse::<f64>().ok().map(|p| p / 100.0),
+            None => a.parse::<f64>().ok(),
+        },
+    }
+}
+
+fn parse_rgb(args: &str) -> Option<Color> {
+    let args = split_args(args);
+    if !(3..=4).contains(&args.len()) {
+        return None;
+    }
+    let channel = |arg: &str| -> Option<u8> {
+        let value = match arg.strip_suffix('%') {
+            Some(p) => p.parse::<f64>().ok()? * 2.55,
+            None => arg.parse::<f64>().ok()?,
+        };
+        Some(value.round().clamp(0.0, 255.0) as u8)
+    };
+    Some(Color::new(
+        channel(args[0])?,
+        channel(args[1])?,
+        channel(args[2])?,
+        parse_alpha(args.get(3))?,
+    ))
+}
+
+fn parse_hsl(args: &str) -> Option<Color> {
+    let args = split_args(args);
+    if !(3..=4).contains(&args.len()) {
+        return None;
+    }
+    let hue = args[0].strip_suffix("deg").unwrap_or(args[0]);
+    let h = hue.parse::<f64>().ok()?.rem_euclid(360.0) / 360.0;
+    let percent = |arg: &str| -> Option<f64> {
+        let p = arg.strip_suffix('%').unwrap_or(arg).parse::<f64>().ok()?;
+        Some((p / 100.0).clamp(0.0, 1.0))
+    };
+    let s = percent(args[1])?;
+    let l = percent(args[2])?;
+
+    // CSS Color Module Level 3, section 4.2.4.
+    let m2 = if l <= 0.5 {
+        l * (s + 1.0)
+    } else {
+        l + s - l * s
+    };
+    let m1 = l * 2.0 - m2;
+    let hue_to_rgb = |h: f64| -> u8 {
+        let h = h.rem_euclid(1.0);
+        let v = if h * 6.0 < 1.0 {
+            m1 + (m2 - m1) * h * 6.0
+        } else if h * 2.0 < 1.0 {
+            m2
+        } else if h * 3.0 < 2.0 {
+            m1 + (m2 - m1) * (2.0 / 3.0 - h) * 6.0
+        } else {
+            m1
+        };
+        (v * 255.0).round().clamp(0.0, 255.0) as u8
+    };
+    Some(Color::new(
+        hue_to_rgb(h + 1.0 / 3.0),
+        hue_to_rgb(h),
+        hue_to_rgb(h - 1.0 / 3.0),
+        parse_alpha(args.get(3))?,
+    ))
+}
+
+/// CSS named colors as `0xRRGGBB`.
+const NAMED_COLORS: &[(&str, u32)] = &[
+    ("aliceblue", 0xf0f8ff),
+    ("antiquewhite", 0xfaebd7),
+    ("aqua", 0x00ffff),
+    ("aquamarine", 0x7fffd4),
+    ("azure", 0xf0ffff),
+    ("beige", 0xf5f5dc),
+    ("bisque", 0xffe4c4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xffebcd),
+    ("blue", 0x0000ff),
+    ("blueviolet", 0x8a2be2),
+    ("brown", 0xa52a2a),
+    ("burlywood", 0xdeb887),
+    ("cadetblue", 0x5f9ea0),
+    ("chartreuse", 0x7fff00),
+    ("chocolate", 0xd2691e),
+    ("coral", 0xff7f50),
+    ("cornflowerblue", 0x6495ed),
+    ("cornsilk", 0xfff8dc),
+    ("crimson", 0xdc143c),
+    ("cyan", 0x00ffff),
+    ("darkblue", 0x00008b),
+    ("darkcyan", 0x008b8b),
+    ("darkgoldenrod", 0xb8860b),
+    ("darkgray", 0xa9a9a9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xa9a9a9),
+    ("darkkhaki", 0xbdb76b),
+    ("darkmagenta", 0x8b008b),
+    ("darkolivegreen", 0x556b2f),
+    ("darkorange", 0xff8c00),
+    ("darkorchid", 0x9932cc),
+    ("darkred", 0x8b0000),
+    ("darksalmon", 0xe9967a),
+    ("darkseagreen", 0x8fbc8f),
+    ("darkslateblue", 0x483d8b),
+    ("darkslategray", 0x2f4f4f),
+    ("darkslategrey", 0x2f4f4f),
+    ("darkturquoise", 0x00ced1),
+    ("darkviolet", 0x9400d3),
+    ("deeppink", 0xff1493),
+    ("deepskyblue", 0x00bfff),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1e90ff),
+    ("firebrick", 0xb22222),
+    ("floralwhite", 0xfffaf0),
+    ("forestgreen", 0x228b22),
+    ("fuchsia", 0xff00ff),
+    ("gainsboro", 0xdcdcdc),
+    ("ghostwhite", 0xf8f8ff),
+    ("gold", 0xffd700),
+    ("goldenrod", 0xdaa520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xadff2f),
+    ("grey", 0x808080),
+    ("honeydew", 0xf0fff0),
+    ("hotpink", 0xff69b4),
+    ("indianred", 0xcd5c5c),
+    ("indigo", 0x4b0082),
+    ("ivory", 0xfffff0),
+    ("khaki", 0xf0e68c),
+    ("lavender", 0xe6e6fa),
+    ("lavenderblush", 0xfff0f5),
+    ("lawngreen", 0x7cfc00),
+    ("lemonchiffon", 0xfffacd),
+    ("lightblue", 0xadd8e6),
+    ("lightcoral", 0xf08080),
+    ("lightcyan", 0xe0ffff),
+    ("lightgoldenrodyellow", 0xfafad2),
+    ("lightgray", 0xd3d3d3),
+    ("lightgreen", 0x90ee90),
+    ("lightgrey", 0xd3d3d3),
+    ("lightpink", 0xffb6c1),
+    ("lightsalmon", 0xffa07a),
+    ("lightseagreen", 0x20b2aa),
+    ("lightskyblue", 0x87cefa),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xb0c4de),
+    ("lightyellow", 0xffffe0),
+    ("lime", 0x00ff00),
+    ("limegreen", 0x32cd32),
+    ("linen", 0xfaf0e6),
+    ("magenta", 0xff00ff),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66cdaa),
+    ("mediumblue", 0x0000cd),
+    ("mediumorchid", 0xba55d3),
+    ("mediumpurple", 0x9370db),
+    ("mediumseagreen", 0x3cb371),
+    ("mediumslateblue", 0x7b68ee),
+    ("mediumspringgreen", 0x00fa9a),
+    ("mediumturquoise", 0x48d1cc),
+    ("mediumvioletred", 0xc71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xf5fffa),
+    ("mistyrose", 0xffe4e1),
+    ("moccasin", 0xffe4b5),
+    ("navajowhite", 0xffdead),
+    ("navy", 0x000080),
+    ("oldlace", 0xfdf5e6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6b8e23),
+    ("orange", 0xffa500),
+    ("orangered", 0xff4500),
+    ("orchid", 0xda70d6),
+    ("palegoldenrod", 0xeee8aa),
+    ("palegreen", 0x98fb98),
+    ("paleturquoise", 0xafeeee),
+    ("palevioletred", 0xdb7093),
+    ("papayawhip", 0xffefd5),
+    ("peachpuff", 0xffdab9),
+    ("peru", 0xcd853f),
+    ("pink", 0xffc0cb),
+    ("plum", 0xdda0dd),
+    ("powderblue", 0xb0e0e6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xff0000),
+    ("rosybrown", 0xbc8f8f),
+    ("royalblue", 0x4169e1),
+    ("saddlebrown", 0x8b4513),
+    ("salmon", 0xfa8072),
+    ("sandybrown", 0xf4a460),
+    ("seagreen", 0x2e8b57),
+    ("seashell", 0xfff5ee),
+    ("sienna", 0xa0522d),
+    ("silver", 0xc0c0c0),
+    ("skyblue", 0x87ceeb),
+    ("slateblue", 0x6a5acd),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xfffafa),
+    ("springgreen", 0x00ff7f),
+    ("steelblue", 0x4682b4),
+    ("tan", 0xd2b48c),
+    ("teal", 0x008080),
+    ("thistle", 0xd8bfd8),
+    ("tomato", 0xff6347),
+    ("turquoise", 0x40e0d0),
+    ("violet", 0xee82ee),
+    ("wheat", 0xf5deb3),
+    ("white", 0xffffff),
+    ("whitesmoke", 0xf5f5f5),
+    ("yellow", 0xffff00),
+    ("yellowgreen", 0x9acd32),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hex_forms() {
+        assert_eq!(Color::parse("#ff8800"), Some(Color::new(255, 136, 0, 1.0)));
+        assert_eq!(Color::parse("#F80"), Some(Color::new(255, 136, 0, 1.0)));
+        assert_eq!(Color::parse("#f808").unwrap().a, 136.0 / 255.0);
+        assert_eq!(Color::parse("#ff880000").unwrap().a, 0.0);
+        assert_eq!(Color::parse("#ff88"), Color::parse("#ffff8888"));
+        assert!(Color::parse("#ff88g0").is_none());
+        assert!(Color::parse("#ff880").is_none());
+    }
+
+    #[test]
+    fn test_parse_rgb_forms() {
+        assert_eq!(
+            Color::parse("rgb(255, 0, 0)"),
+            Some(Color::new(255, 0, 0, 1.0))
+        );
+        assert_eq!(
+            Color::parse("rgba(0,128,255,0.25)"),
+            Some(Color::new(0, 128, 255, 0.25))
+        );
+        assert_eq!(
+            Color::parse("rgb(100% 0% 0% / 50%)"),
+            Some(Color::new(255, 0, 0, 0.5))
+        );
+        assert!(Color::parse("rgb(1, 2)").is_none());
+    }
+
+    #[test]
+    fn test_parse_hsl_forms() {
+        assert_eq!(
+            Color::parse("hsl(120, 100%, 50%)"),
+            Some(Color::new(0, 255, 0, 1.0))
+        );
+        assert_eq!(
+            Color::parse("hsla(240deg, 100%, 25%, 0.5)"),
+            Some(Color::new(0, 0, 128, 0.5))
+        );
+        assert_eq!(
+            Color::parse("hsl(0 0% 100%)"),
+            Some(Color::new(255, 255, 255, 1.0))
+        );
+    }
+
+    #[test]
+    fn test_parse_named_colors() {
+        assert_eq!(Color::parse("Red"), Some(Color::new(255, 0, 0, 1.0)));
+        assert_eq!(Color::parse("rebeccapurple").unwrap().to_hex(), "#663399");
+        assert_eq!(Color::parse("transparent").unwrap().a, 0.0);
+        assert!(Color::parse("not-a-color").is_none());
+    }
+
+    #[test]
+    fn test_display_round_trips_alpha() {
+        let c = Color::parse("rgba(255, 0, 0, 0.5)").unwrap();
+        assert_eq!(c.to_string(), "#ff000080");
+        assert_eq!(Color::parse("red").unwrap().to_string(), "#ff0000");
+        assert!("nope".parse::<Color>().is_err());
+    }
+}
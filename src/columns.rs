@@ -0,0 +1,400 @@
+use crate::{LegendConfig, LegendTheme, error::LegendError};
+use std::ops::Range;
+use svg::node::element::{Group, Line};
+use svg::{Document, Node};
+
+/// A rendered piece of the combined legend: an SVG document with its size.
+pub struct Block {
+    pub svg: Document,
+    pub width: u32,
+    pub height: u32,
+    /// Accessible name of the block's group: the layer label, or the group heading.
+    pub label: Option<String>,
+}
+
+impl Default for Block {
+    fn default() -> Self {
+        Self {
+            svg: Document::new(),
+            width: 0,
+            height: 0,
+            label: None,
+        }
+    }
+}
+
+/// Renders a range of a layer's rows, with the layer title when the flag is set.
+pub type RenderRows<'a> = Box<dyn Fn(Range<usize>, bool) -> Result<Block, LegendError> + 'a>;
+
+/// The rows of a layer that may be split across columns.
+pub struct Rows<'a> {
+    pub count: usize,
+    /// Height the layer title adds on top of the rows (`0` without a title).
+    pub title_height: u32,
+    pub render: RenderRows<'a>,
+}
+
+/// A layer (or composite symbol) of [`crate::MapLibreLegend::render_all`], the unit of
+/// the column flow.
+pub struct FlowItem<'a> {
+    /// Group heading drawn above the item, always in the same column as its first rows.
+    pub heading: Option<Block>,
+    /// Whether a divider separates the item from the one above it.
+    pub starts_section: bool,
+    pub block: Block,
+    /// Set for multi-row layers when [`LegendConfig::split_layers`] is enabled.
+    pub rows: Option<Rows<'a>>,
+}
+
+/// A planned piece of a column: a whole item, or a range of its rows.
+struct Piece {
+    item: usize,
+    rows: Option<Range<usize>>,
+}
+
+/// Distributes the items over columns, in order.
+///
+/// With [`LegendConfig::max_height`], a new column starts whenever the next item would
+/// overflow it. Otherwise the items are balanced over [`LegendConfig::columns`] columns,
+/// using the smallest column height that fits. Items only break between rows when they
+/// carry [`Rows`]; a group heading stays with the item below it.
+///
+/// Returns the blocks of each column, each flagged with whether a divider is drawn above it.
+pub fn flow(
+    items: Vec<FlowItem<'_>>,
+    config: &LegendConfig,
+) -> Result<Vec<Vec<(Block, bool)>>, LegendError> {
+    let capacity = match config.max_height {
+        Some(max_height) => max_height,
+        None if config.columns <= 1 => u32::MAX,
+        None => {
+            let total: u32 = items.iter().map(whole_height).sum();
+            let (mut low, mut high) = (1, total.max(1));
+            while low < high {
+                let mid = low + (high - low) / 2;
+                if plan(&items, mid, config.theme.row_height).len() <= config.columns as usize {
+                    high = mid;
+                } else {
+                    low = mid + 1;
+                }
+            }
+            high
+        }
+    };
+
+    let pieces = plan(&items, capacity, config.theme.row_height);
+    let mut items = items;
+    let mut columns = Vec::new();
+    for column_pieces in pieces {
+        let mut column = Vec::new();
+        for piece in column_pieces {
+            let item = &mut items[piece.item];
+            let first_piece = piece.rows.as_ref().is_none_or(|r| r.start == 0);
+            let mut divider = item.starts_section && first_piece;
+            if first_piece && let Some(heading) = item.heading.take() {
+                column.push((heading, divider));
+                divider = false;
+            }
+            let block = match (&piece.rows, &item.rows) {
+                (Some(range), Some(rows)) => (rows.render)(range.clone(), range.start == 0)?,
+                _ => std::mem::take(&mut item.block),
+            };
+            column.push((block, divider));
+        }
+        columns.push(column);
+    }
+    Ok(columns)
+}
+
+/// Lays the columns out side by side into one document, and returns it with its width
+/// and height.
+///
+/// Dividers span the width of their column.
+pub fn compose(columns: Vec<Vec<(Block, bool)>>, theme: &LegendTheme) -> (Document, u32, u32) {
+    let mut doc = Document::new();
+    let mut index = 0;
+    let mut x = 0;
+    let mut height = 0;
+    for column in columns {
+        let width = column.iter().map(|(b, _)| b.width).max().unwrap_or(0);
+        let mut y = 0;
+        for (block, divider) in column {
+            if divider && y > 0 {
+                doc = doc.add(
+                    Line::new()
+                        .set("x1", x)
+                        .set("y1", y)
+                        .set("x2", x + width)
+                        .set("y2", y)
+                        .set("stroke", theme.divider_color.as_str())
+                        .set("stroke-width", theme.divider_width),
+                );
+            }
+            let block_height = block.height;
+            doc = doc.add(place(block, x, y, index));
+            y += block_height;
+            index += 1;
+        }
+        x += width;
+        height = height.max(y);
+    }
+    (sized(doc, x, height), x, height)
+}
+
+/// Lays the items out left to right into one document, starting a new line when the next
+/// one would pass `default_width`, and returns it with its width and height. A group
+/// heading stays on the line of the item it precedes. Used for
+/// [`crate::Orientation::Horizontal`].
+pub fn compose_lines(items: Vec<FlowItem<'_>>, config: &LegendConfig) -> (Document, u32, u32) {
+    let mut doc = Document::new();
+    let mut index = 0;
+    let (mut x, mut y) = (0, 0);
+    let (mut width, mut line_height) = (0, 0);
+    for item in items {
+        let blocks: Vec<Block> = item.heading.into_iter().chain([item.block]).collect();
+        let item_width: u32 = blocks.iter().map(|b| b.width).sum();
+        if x > 0 && x + item_width > config.default_width {
+            y += line_height;
+            x = 0;
+            line_height = 0;
+        }
+        for block in blocks {
+            let (block_width, block_height) = (block.width, block.height);
+            doc = doc.add(place(block, x, y, index));
+            index += 1;
+            x += block_width;
+            line_height = line_height.max(block_height);
+        }
+        width = width.max(x);
+    }
+    let height = y + line_height;
+    (sized(doc, width, height), width, height)
+}
+
+fn sized(doc: Document, width: u32, height: u32) -> Document {
+    doc.set("width", width)
+        .set("height", height)
+        .set("viewBox", (0, 0, width, height))
+}
+
+/// Moves a block's content into a `<g>` translated to `(x, y)`, named after the block's
+/// label for screen readers. Ids are prefixed with the
+/// block's `index`, so that definitions of different layers (or of pieces of the same
+/// layer) cannot collide in the combined document.
+fn place(block: Block, x: u32, y: u32, index: usize) -> Group {
+    let prefix = format!("l{}-", index);
+    let mut svg = block.svg;
+    let mut group = Group::new().set("transform", format!("translate({},{})", x, y));
+    if let Some(label) = block.label {
+        group = group.set("aria-label", label);
+    }
+    if let (Some(children), Some(content)) = (group.get_children_mut(), svg.get_children_mut()) {
+        for mut child in std::mem::take(content) {
+            namespace_ids(child.as_mut(), &prefix);
+            children.push(child);
+        }
+    }
+    group
+}
+
+/// Prefixes every `id` in the tree, and the `url(#...)` and `#...` references to them.
+fn namespace_ids(node: &mut dyn Node, prefix: &str) {
+    if let Some(attributes) = node.get_attributes_mut() {
+        for (name, value) in attributes.iter_mut() {
+            let renamed = if name == "id" {
+                format!("{}{}", prefix, value)
+            } else if value.contains("url(#") {
+                value.replace("url(#", &format!("url(#{}", prefix))
+            } else if matches!(name.as_str(), "href" | "xlink:href") && value.starts_with('#') {
+                format!("#{}{}", prefix, &value[1..])
+            } else {
+                continue;
+            };
+            *value = renamed.into();
+        }
+    }
+    if let Some(children) = node.get_children_mut() {
+        for child in children {
+            namespace_ids(child.as_mut(), prefix);
+        }
+    }
+}
+
+fn whole_height(item: &FlowItem) -> u32 {
+    item.heading.as_ref().map_or(0, |h| h.height) + item.block.height
+}
+
+/// Estimated height of a range of rows: the full block minus the rows left out, and minus
+/// the title for continuation pieces.
+fn rows_height(item: &FlowItem, rows: &Rows, range: &Range<usize>, row_height: u32) -> u32 {
+    let title = if range.start > 0 {
+        rows.title_height
+    } else {
+        0
+    };
+    let left_out = (rows.count - range.len()) as u32 * row_height;
+    item.block.height.saturating_sub(left_out + title)
+}
+
+/// Greedily fills columns of at most `capacity` pixels. An item that does not fit starts
+/// a new column, or is split between rows when it has [`Rows`]; a single item or row
+/// taller than `capacity` gets a column of its own.
+fn plan(items: &[FlowItem], capacity: u32, row_height: u32) -> Vec<Vec<Piece>> {
+    let mut columns = Vec::new();
+    let mut column = Vec::new();
+    let mut used = 0;
+    for (i, item) in items.iter().enumerate() {
+        let whole = whole_height(item);
+        let rows = match item.rows.as_ref().filter(|r| r.count > 1) {
+            Some(rows) if used + whole > capacity => rows,
+            _ => {
+                if used > 0 && used + whole > capacity {
+                    columns.push(std::mem::take(&mut column));
+                    used = 0;
+                }
+                column.push(Piece {
+                    item: i,
+                    rows: None,
+                });
+                used += whole;
+                continue;
+            }
+        };
+        let heading = item.heading.as_ref().map_or(0, |h| h.height);
+        let mut start = 0;
+        while start < rows.count {
+            let head = if start == 0 { heading } else { 0 };
+            let height = |end: usize| head + rows_height(item, rows, &(start..end), row_height);
+            if used > 0 && used + height(start + 1) > capacity {
+                columns.push(std::mem::take(&mut column));
+                used = 0;
+            }
+            let mut end = start + 1;
+            while end < rows.count && used + height(end + 1) <= capacity {
+                end += 1;
+            }
+            used += height(end);
+            column.push(Piece {
+                item: i,
+                rows: (start > 0 || end < rows.count).then_some(start..end),
+            });
+            start = end;
+            if start < rows.count {
+                columns.push(std::mem::take(&mut column));
+                used = 0;
+            }
+        }
+    }
+    if !column.is_empty() || columns.is_empty() {
+        columns.push(column);
+    }
+    columns
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(height: u32, heading: Option<u32>) -> FlowItem<'static> {
+        let block = |height| Block {
+            svg: Document::new(),
+            width: 100,
+            height,
+            label: None,
+        };
+        FlowItem {
+            heading: heading.map(block),
+            starts_section: true,
+            block: block(height),
+            rows: None,
+        }
+    }
+
+    fn heights(columns: &[Vec<(Block, bool)>]) -> Vec<u32> {
+        columns
+            .iter()
+            .map(|c| c.iter().map(|(b, _)| b.height).sum())
+            .collect()
+    }
+
+    #[test]
+    fn test_flow_balances_columns() {
+        let config = LegendConfig {
+            columns: 2,
+            ..Default::default()
+        };
+        let items = vec![
+            item(70, None),
+            item(40, None),
+            item(40, None),
+            item(70, None),
+        ];
+        let columns = flow(items, &config).unwrap();
+        assert_eq!(heights(&columns), vec![110, 110]);
+    }
+
+    #[test]
+    fn test_flow_max_height_adds_columns() {
+        let config = LegendConfig {
+            max_height: Some(110),
+            ..Default::default()
+        };
+        let items = vec![item(70, None), item(70, None), item(40, None)];
+        let columns = flow(items, &config).unwrap();
+        assert_eq!(heights(&columns), vec![70, 110]);
+    }
+
+    #[test]
+    fn test_flow_keeps_heading_with_item() {
+        let config = LegendConfig {
+            max_height: Some(100),
+            ..Default::default()
+        };
+        let items = vec![item(70, None), item(40, Some(30))];
+        let columns = flow(items, &config).unwrap();
+        assert_eq!(columns.len(), 2);
+        assert_eq!(heights(&columns), vec![70, 70]);
+        // The divider above the new column's first block is dropped when composing.
+        let (doc, width, height) = compose(columns, &LegendTheme::default());
+        assert!(!doc.to_string().contains("<line"));
+        assert_eq!((width, height), (200, 70));
+    }
+
+    #[test]
+    fn test_compose_lines_wraps_at_default_width() {
+        let items = vec![item(40, None), item(70, None), item(40, Some(30))];
+        let config = LegendConfig {
+            default_width: 250,
+            ..Default::default()
+        };
+        let (doc, width, height) = compose_lines(items, &config);
+        let body = doc.to_string();
+        assert!(body.contains("translate(100,0)"));
+        // The heading wraps with its item.
+        assert!(body.contains("translate(0,70)"));
+        assert!(body.contains("translate(100,70)"));
+        assert_eq!((width, height), (200, 110));
+    }
+
+    #[test]
+    fn test_compose_namespaces_ids() {
+        use svg::node::element::{Definitions, LinearGradient, Rectangle};
+        let block = || Block {
+            svg: Document::new()
+                .add(Definitions::new().add(LinearGradient::new().set("id", "g")))
+                .add(Rectangle::new().set("fill", "url(#g)")),
+            width: 100,
+            height: 40,
+            label: Some("Water".to_string()),
+        };
+        let columns = vec![vec![(block(), false), (block(), false)]];
+        let svg = compose(columns, &LegendTheme::default()).0.to_string();
+        assert!(svg.contains(r#"id="l0-g""#));
+        assert!(svg.contains(r#"id="l1-g""#));
+        assert!(svg.contains("url(#l0-g)"));
+        assert!(svg.contains("url(#l1-g)"));
+        assert!(svg.contains(r#"aria-label="Water""#));
+        // Block documents are moved into groups, not nested.
+        assert_eq!(svg.matches("<svg").count(), 1);
+    }
+}
@@ -1,27 +1,22 @@
+use crate::LegendConfig;
+use crate::LegendTheme;
+use crate::color::Color;
 use crate::error::LegendError;
+use crate::layout::{fit_label, render_lines};
+use crate::locale::{Words, localized, words};
+use crate::numbers::NumberFormat;
 use base64::{Engine as _, engine::general_purpose::STANDARD};
 use image::{DynamicImage, GenericImageView, ImageFormat};
 use serde::Deserialize;
 use serde_json::{Map, Value};
+use std::collections::BTreeMap;
 use std::io::Cursor;
 use svg::Document;
-use svg::node::element::{Line, Text as SvgText};
+use svg::node::element::{Image, Line, Pattern, Text as SvgText};
 
 /// Fallback color used when a paint expression value cannot be resolved to a valid color.
 pub const FALLBACK_COLOR: &str = "#cccccc";
 
-/// Height in pixels allocated per legend row (icon + label).
-pub const ROW_HEIGHT: u32 = 30;
-
-/// Height in pixels of the visual icon element (rect, circle) inside each row.
-pub const ICON_HEIGHT: u32 = 20;
-
-/// Left and top margin in pixels for icon and label elements.
-pub const PADDING: u32 = 10;
-
-/// Font size in pixels for legend label text.
-pub const FONT_SIZE: u32 = 14;
-
 enum ExpressionKind {
     Match,
     Case,
@@ -29,6 +24,8 @@ enum ExpressionKind {
     Step,
     Coalesce,
     Literal,
+    Let,
+    Color,
 }
 
 impl ExpressionKind {
@@ -40,6 +37,8 @@ impl ExpressionKind {
             "step" => Ok(Self::Step),
             "coalesce" => Ok(Self::Coalesce),
             "literal" => Ok(Self::Literal),
+            "let" => Ok(Self::Let),
+            "rgb" | "rgba" | "to-color" => Ok(Self::Color),
             _ => Err(LegendError::InvalidExpression(format!(
                 "Unknown expression type: {}",
                 s
@@ -48,15 +47,36 @@ impl ExpressionKind {
     }
 }
 
+/// A layer of the style, with the fields the legends are drawn from.
 #[derive(Debug, Deserialize, Clone)]
 pub struct Layer {
+    /// Unique layer id.
     pub id: String,
+    /// Layer type: `fill`, `line`, `circle`, `symbol`, ...
     #[serde(rename = "type")]
     pub layer_type: String,
+    /// Name of the source in [`Style::sources`] the layer draws from.
+    #[serde(default)]
+    pub source: Option<String>,
+    /// Layer of a vector tile source the layer draws from.
+    #[serde(default, rename = "source-layer")]
+    pub source_layer: Option<String>,
+    /// Minimum zoom level at which the layer is drawn.
+    #[serde(default)]
+    pub minzoom: Option<f64>,
+    /// Zoom level from which the layer is no longer drawn.
+    #[serde(default)]
+    pub maxzoom: Option<f64>,
+    /// Feature filter expression.
+    #[serde(default)]
+    pub filter: Option<serde_json::Value>,
+    /// Paint properties, as raw JSON.
     #[serde(default)]
     pub paint: Option<serde_json::Value>,
+    /// Layout properties, as raw JSON.
     #[serde(default)]
     pub layout: Option<serde_json::Value>,
+    /// Arbitrary metadata; the legend options live in its `legend` object.
     #[serde(default)]
     pub metadata: Option<serde_json::Value>,
 }
@@ -85,13 +105,83 @@ where
     })
 }
 
-#[derive(Debug, Deserialize)]
+/// A MapLibre style, with the fields the legends are drawn from.
+///
+/// Available through [`crate::MapLibreLegend::style`].
+#[derive(Debug, Deserialize, Clone)]
 pub struct Style {
+    /// Style specification version (`8`).
+    #[serde(default)]
+    pub version: Option<u64>,
+    /// Human-readable name of the style.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Arbitrary style-wide metadata.
+    #[serde(default)]
+    pub metadata: Option<Value>,
+    /// Data sources, by name.
+    #[serde(default)]
+    pub sources: BTreeMap<String, Source>,
+    /// URL template of the glyph (font) PBFs.
+    #[serde(default)]
+    pub glyphs: Option<String>,
+    /// Layers, in drawing order.
     pub layers: Vec<Layer>,
+    /// Sprite URLs; a single `sprite` URL becomes one entry.
     #[serde(default, deserialize_with = "deserialize_sprite_urls")]
     pub sprite: Vec<String>,
 }
 
+impl Style {
+    /// Returns the layer with the given id.
+    pub fn layer(&self, id: &str) -> Option<&Layer> {
+        self.layers.iter().find(|l| l.id == id)
+    }
+
+    /// Returns the source a layer draws from.
+    pub fn layer_source(&self, layer: &Layer) -> Option<&Source> {
+        layer
+            .source
+            .as_ref()
+            .and_then(|name| self.sources.get(name))
+    }
+}
+
+/// A data source declared in the style's `sources`.
+#[derive(Debug, Deserialize, Clone)]
+pub struct Source {
+    /// Source type: `vector`, `raster`, `raster-dem`, `geojson`, `image`, `video`.
+    #[serde(default, rename = "type")]
+    pub source_type: String,
+    /// Attribution text, possibly HTML, to show with data from the source.
+    #[serde(default)]
+    pub attribution: Option<String>,
+    /// URL of a TileJSON (or of the data of `image` and `video` sources).
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Tile URL templates.
+    #[serde(default)]
+    pub tiles: Vec<String>,
+    /// Whether the points of a `geojson` source are clustered.
+    #[serde(default)]
+    pub cluster: bool,
+    /// Radius in pixels of each cluster.
+    #[serde(default, rename = "clusterRadius")]
+    pub cluster_radius: Option<f64>,
+    /// Zoom level up to which points are clustered.
+    #[serde(default, rename = "clusterMaxZoom")]
+    pub cluster_max_zoom: Option<f64>,
+    /// Minimum number of points forming a cluster.
+    #[serde(default, rename = "clusterMinPoints")]
+    pub cluster_min_points: Option<u64>,
+    /// Aggregates computed over the clustered points.
+    #[serde(default, rename = "clusterProperties")]
+    pub cluster_properties: Option<Value>,
+    /// Data of a `geojson` source: inline GeoJSON, or the URL of a GeoJSON file.
+    #[serde(default)]
+    pub data: Option<Value>,
+}
+
 pub fn get_legend_object(layer: &Layer) -> Result<Option<&Map<String, Value>>, LegendError> {
     let metadata = match layer.metadata.as_ref() {
         Some(m) => m,
@@ -110,30 +200,69 @@ pub fn get_legend_object(layer: &Layer) -> Result<Option<&Map<String, Value>>, L
     Ok(Some(legend_obj))
 }
 
-pub fn get_layer_label(layer: &Layer) -> Result<String, LegendError> {
-    let legend = get_legend_object(layer)?;
-    let label = legend
-        .and_then(|l| l.get("label"))
-        .and_then(|l| l.as_str())
-        .map(|s| s.to_string())
-        .unwrap_or_else(|| layer.id.clone());
-    Ok(label)
+/// Returns the layer's display label.
+///
+/// Uses `metadata.legend.label` when present, localized for [`LegendConfig::locale`] (see
+/// [`localized`]). Otherwise, if
+/// [`LegendConfig::label_from_filter`] is enabled and the layer has a `filter`, the
+/// filter is described via [`format_condition`] (e.g. `"class == park"`). Falls back to
+/// the layer id.
+pub fn get_layer_label(layer: &Layer, config: &LegendConfig) -> Result<String, LegendError> {
+    if let Some(label) = legend_str(layer, config, "label")? {
+        return Ok(label.to_string());
+    }
+    if config.label_from_filter
+        && let Some(filter) = layer.filter.as_ref()
+        && let Ok(Some(label)) = format_condition(filter, config)
+    {
+        return Ok(label);
+    }
+    Ok(layer.id.clone())
 }
 
-pub fn get_layer_default_label(layer: &Layer) -> Result<String, LegendError> {
-    let legend = get_legend_object(layer)?;
-    let default_label = legend
-        .and_then(|l| l.get("default"))
-        .and_then(|l| l.as_str())
+/// Returns the name of the legend section the layer belongs to, if any.
+///
+/// Uses `metadata.legend.group`, localized like the label. When [`LegendConfig::group_by_source_layer`] is enabled,
+/// layers without an explicit group fall back to their `source-layer`.
+pub fn get_layer_group(
+    layer: &Layer,
+    config: &LegendConfig,
+) -> Result<Option<String>, LegendError> {
+    if let Some(group) = legend_str(layer, config, "group")? {
+        return Ok(Some(group.to_string()));
+    }
+    if config.group_by_source_layer {
+        return Ok(layer.source_layer.clone());
+    }
+    Ok(None)
+}
+
+/// Returns the string `metadata.legend` option `key` of a layer, localized for
+/// [`LegendConfig::locale`].
+pub fn legend_str<'a>(
+    layer: &'a Layer,
+    config: &LegendConfig,
+    key: &str,
+) -> Result<Option<&'a str>, LegendError> {
+    Ok(get_legend_object(layer)?
+        .and_then(|l| localized(l, key, config.locale.as_deref()))
+        .and_then(|v| v.as_str()))
+}
+
+pub fn get_layer_default_label(
+    layer: &Layer,
+    config: &LegendConfig,
+) -> Result<String, LegendError> {
+    let default_label = legend_str(layer, config, "default")?
         .map(|s| s.to_string())
         .unwrap_or_else(|| layer.id.clone());
     Ok(default_label)
 }
 
-pub fn get_custom_labels(layer: &Layer) -> Result<Vec<String>, LegendError> {
+pub fn get_custom_labels(layer: &Layer, config: &LegendConfig) -> Result<Vec<String>, LegendError> {
     let legend = get_legend_object(layer)?;
     let custom_labels = legend
-        .and_then(|l| l.get("custom-labels"))
+        .and_then(|l| localized(l, "custom-labels", config.locale.as_deref()))
         .and_then(|l| l.as_array())
         .map(|arr| {
             arr.iter()
@@ -223,12 +352,31 @@ fn get_single_sprite_sync(sprite_url: &str) -> Result<(DynamicImage, Value), Leg
     Ok((sprite_img, sprite_json))
 }
 
+/// A single icon cut out of a spritesheet, ready to be embedded in an SVG.
+pub struct SpriteIcon {
+    /// Base64-encoded PNG data URL of the icon.
+    pub data_url: String,
+    /// Icon width in CSS pixels (sprite pixels divided by `pixelRatio`).
+    pub width: f64,
+    /// Icon height in CSS pixels (sprite pixels divided by `pixelRatio`).
+    pub height: f64,
+}
+
 /// Searches all loaded spritesheets for `icon_name` and returns a base64-encoded PNG data URL.
 /// Spritesheets are checked in order; the first match wins.
 pub fn get_icon_data_url(
     sprites: &[(DynamicImage, Value)],
     icon_name: &str,
 ) -> Result<String, LegendError> {
+    get_sprite_icon(sprites, icon_name).map(|icon| icon.data_url)
+}
+
+/// Searches all loaded spritesheets for `icon_name` and returns the icon with its size.
+/// Spritesheets are checked in order; the first match wins.
+pub fn get_sprite_icon(
+    sprites: &[(DynamicImage, Value)],
+    icon_name: &str,
+) -> Result<SpriteIcon, LegendError> {
     for (sprite_img, sprite_json) in sprites {
         if let Some(icon_info) = sprite_json.get(icon_name) {
             return extract_icon_from_sprite(sprite_img, icon_info, icon_name);
@@ -244,7 +392,7 @@ fn extract_icon_from_sprite(
     sprite_img: &DynamicImage,
     icon_info: &Value,
     icon_name: &str,
-) -> Result<String, LegendError> {
+) -> Result<SpriteIcon, LegendError> {
     let x = icon_info.get("x").and_then(|v| v.as_u64()).ok_or_else(|| {
         LegendError::InvalidJson(format!("Invalid 'x' field for icon '{}'", icon_name))
     })? as u32;
@@ -263,6 +411,11 @@ fn extract_icon_from_sprite(
         .ok_or_else(|| {
             LegendError::InvalidJson(format!("Invalid 'height' field for icon '{}'", icon_name))
         })? as u32;
+    let pixel_ratio = icon_info
+        .get("pixelRatio")
+        .and_then(|v| v.as_f64())
+        .filter(|r| *r > 0.0)
+        .unwrap_or(1.0);
 
     let icon_img = sprite_img.view(x, y, width, height).to_image();
 
@@ -273,80 +426,196 @@ fn extract_icon_from_sprite(
         .map_err(LegendError::ImageLoad)?;
 
     let base64 = STANDARD.encode(&buf);
-    Ok(format!("data:image/png;base64,{}", base64))
+    Ok(SpriteIcon {
+        data_url: format!("data:image/png;base64,{}", base64),
+        width: width as f64 / pixel_ratio,
+        height: height as f64 / pixel_ratio,
+    })
+}
+
+/// Builds an SVG `<pattern>` that tiles the sprite icon `icon_name`, anchored at `(x, y)`.
+///
+/// Used for `fill-pattern` and `background-pattern`; reference it with `url(#id)`.
+pub fn sprite_pattern(
+    sprites: &[(DynamicImage, Value)],
+    icon_name: &str,
+    id: &str,
+    x: u32,
+    y: u32,
+) -> Result<Pattern, LegendError> {
+    let icon = get_sprite_icon(sprites, icon_name)?;
+    Ok(Pattern::new()
+        .set("id", id)
+        .set("patternUnits", "userSpaceOnUse")
+        .set("x", x)
+        .set("y", y)
+        .set("width", icon.width)
+        .set("height", icon.height)
+        .add(
+            Image::new()
+                .set("width", icon.width)
+                .set("height", icon.height)
+                .set("href", icon.data_url),
+        ))
+}
+
+/// Returns whether `icon_name` names an icon of the loaded spritesheets. The empty name, a
+/// common "no pattern" output of data-driven `*-pattern` expressions, never does.
+pub fn has_sprite_icon(sprites: &[(DynamicImage, Value)], icon_name: &str) -> bool {
+    !icon_name.is_empty()
+        && sprites
+            .iter()
+            .any(|(_, json)| json.get(icon_name).is_some())
+}
+
+/// Parses a `fill-pattern`, `line-pattern` or `background-pattern` value into rows, or
+/// returns `None` when none of its icons is in the loaded spritesheets (no sprite, unknown
+/// icons or only `""`), in which case the layer is drawn with its color instead.
+pub fn pattern_cases(
+    layer: &Layer,
+    config: &LegendConfig,
+    expr: &Value,
+    sprites: &[(DynamicImage, Value)],
+) -> Result<Option<Vec<(String, String)>>, LegendError> {
+    let cases = parse_expression(layer, config, expr)?;
+    let drawable = cases.iter().any(|(_, icon)| has_sprite_icon(sprites, icon));
+    Ok(drawable.then_some(cases))
+}
+
+/// Builds an SVG element id that is unique to `layer` and safe to use in `url(#...)`.
+pub fn svg_id(layer: &Layer, suffix: &str) -> String {
+    format!("{}-{}", layer.id, suffix)
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
 }
 
 pub fn render_label(
     layer: &Layer,
+    config: &LegendConfig,
     doc: &mut Document,
     x: Option<u32>,
     y: Option<u32>,
     is_bold: Option<bool>,
 ) -> Result<(), LegendError> {
-    let label = get_layer_label(layer)?;
-    let x = x.unwrap_or(55);
-    let y = y.unwrap_or(25);
+    let theme = &config.theme;
+    let label = get_layer_label(layer, config)?;
     let is_bold = is_bold.unwrap_or_default();
-    let font_weight = if is_bold { "bold" } else { "normal" };
-
-    *doc = doc.clone().add(
-        SvgText::new("")
-            .set("x", x)
-            .set("y", y)
-            .set("font-size", FONT_SIZE)
-            .set("fill", "black")
-            .set("font-weight", font_weight)
-            .add(svg::node::Text::new(label)),
+    let lines = fit_label(config, &label, is_bold, false);
+    render_lines(
+        doc,
+        theme,
+        &label,
+        &lines,
+        x.unwrap_or(theme.inline_label_x()),
+        y.map_or(theme.baseline(theme.padding as i32), |y| y as i32),
+        is_bold,
     );
     Ok(())
 }
 
-pub fn render_separator(doc: &mut Document, default_width: u32, x: u32, y: u32) {
+/// Adds a text element at `(x, y)` to the document, in the theme's label font, or its
+/// title font when `is_bold` is set.
+pub fn render_text(
+    doc: &mut Document,
+    theme: &LegendTheme,
+    text: &str,
+    x: impl Into<svg::node::Value>,
+    y: impl Into<svg::node::Value>,
+    is_bold: bool,
+) {
+    *doc = doc
+        .clone()
+        .add(text_element(theme, x, y, is_bold).add(svg::node::Text::new(text)));
+}
+
+/// Creates an empty text element at `(x, y)` in the theme's label or title font.
+pub fn text_element(
+    theme: &LegendTheme,
+    x: impl Into<svg::node::Value>,
+    y: impl Into<svg::node::Value>,
+    is_bold: bool,
+) -> SvgText {
+    let (font_size, font_weight) = if is_bold {
+        (theme.title_font_size, theme.title_font_weight.as_str())
+    } else {
+        (theme.font_size, theme.font_weight.as_str())
+    };
+
+    let mut element = SvgText::new("")
+        .set("x", x)
+        .set("y", y)
+        .set("font-size", font_size)
+        .set("fill", theme.text_color.as_str())
+        .set("font-weight", font_weight);
+    if let Some(family) = &theme.font_family {
+        element = element.set("font-family", family.as_str());
+    }
+    element
+}
+
+/// Draws the separator below a title row whose top is at `y`, centered over `default_width`.
+pub fn render_separator(
+    doc: &mut Document,
+    theme: &LegendTheme,
+    default_width: u32,
+    x: u32,
+    y: u32,
+) {
     let line_width = (default_width as f32 * 0.90) as u32;
     let line_x1 = x + (default_width - line_width) / 2;
     let line_x2 = line_x1 + line_width;
-    let line_y = y + 16;
+    let line_y = y + theme.separator_y() - theme.padding;
 
     let line = Line::new()
         .set("x1", line_x1)
         .set("x2", line_x2)
         .set("y1", line_y)
         .set("y2", line_y)
-        .set("stroke", "#999999")
-        .set("stroke-width", 0.5);
+        .set("stroke", theme.separator_color.as_str())
+        .set("stroke-width", theme.separator_width);
 
     *doc = doc.clone().add(line);
 }
 
+/// Normalizes a CSS color into an SVG paint value and an effective opacity.
+///
+/// The color's own alpha (from `#rrggbbaa`, `rgba(...)`, `hsla(...)`, `transparent`, ...)
+/// is multiplied into `base_opacity`. The paint value is always `#rrggbb`, or `"none"` when
+/// the result is fully transparent. Strings that are not valid colors are passed through
+/// unchanged with `base_opacity`.
 pub fn get_fill_and_opacity(color: &str, base_opacity: f64) -> (String, f64) {
-    let trimmed = color.trim_start_matches('#');
-    let len = trimmed.len();
-    if len == 6 {
-        (format!("#{}", trimmed), base_opacity)
-    } else if len == 8 {
-        let rgb = &trimmed[0..6];
-        let alpha_hex = &trimmed[6..8];
-        let alpha = match u8::from_str_radix(alpha_hex, 16) {
-            Ok(val) => val as f64 / 255.0,
-            Err(_) => 1.0,
-        };
-        let effective = alpha * base_opacity;
-        if effective == 0.0 {
-            ("none".to_string(), 1.0)
-        } else {
-            (format!("#{}", rgb), effective)
+    match Color::parse(color) {
+        Some(c) => {
+            let effective = c.a * base_opacity;
+            if effective == 0.0 {
+                ("none".to_string(), 1.0)
+            } else {
+                (c.to_hex(), effective)
+            }
         }
-    } else {
-        // Fallback for non-hex or invalid; assume opaque
-        (color.to_string(), base_opacity)
+        None => (color.to_string(), base_opacity),
     }
 }
 
+/// Resolves a paint value to a color string.
+///
+/// Plain strings are returned as-is. Arrays are evaluated as constant expressions
+/// (`rgb`, `rgba`, `to-color`, `let`/`var`, ...) and fall back to [`FALLBACK_COLOR`]
+/// when they depend on feature data.
 pub fn extract_color(value: Option<&serde_json::Value>) -> Result<String, LegendError> {
     let value = value.ok_or_else(|| LegendError::InvalidJson("Missing JSON value".to_string()))?;
     match value {
         serde_json::Value::String(s) => Ok(s.clone()),
-        serde_json::Value::Array(_) => Ok(FALLBACK_COLOR.to_string()),
+        serde_json::Value::Array(_) => {
+            Ok(output_value(value).unwrap_or_else(|| FALLBACK_COLOR.to_string()))
+        }
         _ => Err(LegendError::InvalidJson(format!(
             "JSON value is neither a string nor an array: {:?}",
             value
@@ -354,6 +623,152 @@ pub fn extract_color(value: Option<&serde_json::Value>) -> Result<String, Legend
     }
 }
 
+/// Returns the color of a paint value if it does not depend on feature data: a plain
+/// string or a constant color expression. Returns `None` for data-driven expressions.
+pub fn constant_color(value: Option<&serde_json::Value>) -> Option<String> {
+    match value? {
+        serde_json::Value::String(s) => Some(s.clone()),
+        value @ serde_json::Value::Array(_) => output_value(value),
+        _ => None,
+    }
+}
+
+/// Converts an expression output slot (a color, icon name, number, ...) into its string form.
+///
+/// Constant sub-expressions are evaluated first (see [`eval_constant`]), so outputs such as
+/// `["rgb", 255, 0, 0]` resolve to a real color. Returns `None` for values that depend on
+/// feature data.
+fn output_value(value: &Value) -> Option<String> {
+    match eval_constant(value)? {
+        Value::String(s) => Some(s),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// Evaluates an expression that does not depend on feature data to a literal value.
+///
+/// Supports `literal`, `let`/`var`, the color constructors `rgb`, `rgba` and `to-color`,
+/// and the arithmetic operators `+`, `-`, `*`, `/`. Colors are returned as strings in
+/// [`Color`]'s `Display` form. Returns `None` for anything else, including a malformed
+/// `let` (which [`parse_expression`] rejects up front).
+fn eval_constant(value: &Value) -> Option<Value> {
+    let arr = match value {
+        Value::Array(arr) => arr,
+        Value::Null | Value::Object(_) => return None,
+        _ => return Some(value.clone()),
+    };
+    let number = |v: &Value| eval_constant(v).and_then(|v| v.as_f64());
+    match arr.first()?.as_str()? {
+        "literal" => arr.get(1).cloned(),
+        "let" => eval_constant(&inline_let(value).ok()?),
+        "rgb" | "rgba" => {
+            if !(4..=5).contains(&arr.len()) {
+                return None;
+            }
+            let channel = |v: &Value| number(v).map(|n| n.round().clamp(0.0, 255.0) as u8);
+            let alpha = match arr.get(4) {
+                Some(a) => number(a)?,
+                None => 1.0,
+            };
+            let color = Color::new(
+                channel(&arr[1])?,
+                channel(&arr[2])?,
+                channel(&arr[3])?,
+                alpha,
+            );
+            Some(Value::String(color.to_string()))
+        }
+        // Each argument is tried in order; the first one that is a valid color wins.
+        "to-color" => arr.iter().skip(1).find_map(|arg| {
+            let color = match eval_constant(arg)? {
+                Value::String(s) => Color::parse(&s)?,
+                Value::Array(c) if (3..=4).contains(&c.len()) => {
+                    let channel =
+                        |i: usize| c[i].as_f64().map(|n| n.round().clamp(0.0, 255.0) as u8);
+                    let alpha = c.get(3).and_then(|a| a.as_f64()).unwrap_or(1.0);
+                    Color::new(channel(0)?, channel(1)?, channel(2)?, alpha)
+                }
+                _ => return None,
+            };
+            Some(Value::String(color.to_string()))
+        }),
+        op @ ("+" | "-" | "*" | "/") => {
+            let args = arr
+                .iter()
+                .skip(1)
+                .map(number)
+                .collect::<Option<Vec<f64>>>()?;
+            let result = match (op, args.as_slice()) {
+                ("-", [a]) => -a,
+                ("+", _) => args.iter().sum(),
+                ("*", _) => args.iter().product(),
+                ("-", [a, b]) => a - b,
+                ("/", [a, b]) if *b != 0.0 => a / b,
+                _ => return None,
+            };
+            serde_json::Number::from_f64(result).map(Value::Number)
+        }
+        _ => None,
+    }
+}
+
+/// Rewrites `["let", name, value, ..., body]` expressions by substituting each
+/// `["var", name]` in `body` with its bound value, recursively.
+///
+/// Fails on a `let` without a body or with a name missing its value, which would otherwise
+/// be left in place and parsed again.
+fn inline_let(value: &Value) -> Result<Value, LegendError> {
+    inline_vars(value, &Map::new())
+}
+
+/// Checks that every `let` in an expression has a body and a value for each name, so that
+/// [`inline_let`] and [`eval_constant`] cannot fail on it.
+fn check_let(value: &Value) -> Result<(), LegendError> {
+    let Some(arr) = value.as_array() else {
+        return Ok(());
+    };
+    match arr.first().and_then(|v| v.as_str()) {
+        Some("literal") => Ok(()),
+        Some("let") if arr.len() < 2 || !arr.len().is_multiple_of(2) => Err(
+            LegendError::InvalidExpression("Malformed 'let' expression".to_string()),
+        ),
+        _ => arr.iter().try_for_each(check_let),
+    }
+}
+
+fn inline_vars(value: &Value, scope: &Map<String, Value>) -> Result<Value, LegendError> {
+    let Some(arr) = value.as_array() else {
+        return Ok(value.clone());
+    };
+    match arr.first().and_then(|v| v.as_str()) {
+        Some("let") => {
+            if arr.len() < 2 || !arr.len().is_multiple_of(2) {
+                return Err(LegendError::InvalidExpression(
+                    "Malformed 'let' expression".to_string(),
+                ));
+            }
+            let mut inner = scope.clone();
+            for pair in arr[1..arr.len() - 1].chunks(2) {
+                if let Some(name) = pair[0].as_str() {
+                    inner.insert(name.to_string(), inline_vars(&pair[1], &inner)?);
+                }
+            }
+            inline_vars(&arr[arr.len() - 1], &inner)
+        }
+        Some("var") => Ok(arr
+            .get(1)
+            .and_then(|v| v.as_str())
+            .and_then(|name| scope.get(name))
+            .cloned()
+            .unwrap_or_else(|| value.clone())),
+        // Literal payloads are data, not expressions.
+        Some("literal") => Ok(value.clone()),
+        _ => arr.iter().map(|v| inline_vars(v, scope)).collect(),
+    }
+}
+
 /// Extracts the feature property name from a MapLibre input expression.
 ///
 /// Handles `["get", "field"]` directly, and recursively unwraps string transforms
@@ -381,6 +796,8 @@ fn extract_field(expr: &serde_json::Value) -> Result<&str, LegendError> {
                     )
                 })
             }
+            "geometry-type" => Ok("geometry type"),
+            "id" => Ok("id"),
             "downcase" | "upcase" | "to-string" | "to-number" => {
                 if arr.len() != 2 {
                     return Err(LegendError::InvalidExpression(format!(
@@ -402,16 +819,101 @@ fn extract_field(expr: &serde_json::Value) -> Result<&str, LegendError> {
     }
 }
 
+/// Describes the numeric input of a `step` or `interpolate` expression for labels:
+///
+/// - `["get", field]` and `["feature-state", field]` as the property name, `["zoom"]` as
+///   `zoom`, `["id"]` as `id`, and numbers as themselves;
+/// - type conversions and assertions (`to-number`, `number`) as their first argument, and
+///   `coalesce` as its first argument that reads feature data (`["coalesce", ["get",
+///   "a"], 0]` is `a`);
+/// - arithmetic as an infix expression, e.g. `pop / area`, with nested operations in
+///   parentheses, and math functions as calls, e.g. `sqrt(area)`;
+/// - `let` / `var` by inlining the variables.
+///
+/// Returns `None` for inputs that cannot be described.
+pub fn numeric_input_field(input: &Value) -> Option<String> {
+    let arr = match input {
+        Value::Number(n) => return Some(n.to_string()),
+        Value::Array(arr) => arr,
+        _ => return None,
+    };
+    let op = arr.first()?.as_str()?;
+    let args = &arr[1..];
+    match (op, args) {
+        ("get" | "feature-state", [field, ..]) => field.as_str().map(|f| f.to_string()),
+        ("zoom", []) => Some("zoom".to_string()),
+        ("id", []) => Some("id".to_string()),
+        ("literal", [value]) if value.is_number() => numeric_input_field(value),
+        ("let", _) => numeric_input_field(&inline_let(input).ok()?),
+        ("to-number" | "number", [first, ..]) => numeric_input_field(first),
+        ("coalesce", _) => args
+            .iter()
+            .find(|arg| arg.is_array())
+            .or_else(|| args.first())
+            .and_then(numeric_input_field),
+        ("-", [value]) => Some(format!("-{}", arithmetic_operand(value)?)),
+        ("+" | "-" | "*" | "/" | "%" | "^", [_, _, ..]) => {
+            let operands = args
+                .iter()
+                .map(arithmetic_operand)
+                .collect::<Option<Vec<String>>>()?;
+            Some(operands.join(&format!(" {} ", op)))
+        }
+        (
+            "abs" | "ceil" | "floor" | "round" | "sqrt" | "ln" | "log10" | "log2" | "length",
+            [value],
+        ) => Some(format!("{}({})", op, numeric_input_field(value)?)),
+        _ => None,
+    }
+}
+
+/// Describes an operand of an arithmetic input, parenthesizing nested operations.
+fn arithmetic_operand(value: &Value) -> Option<String> {
+    let field = numeric_input_field(value)?;
+    let is_operation = value.as_array().is_some_and(|a| {
+        a.len() > 2
+            && matches!(
+                a.first().and_then(|v| v.as_str()),
+                Some("+" | "-" | "*" | "/" | "%" | "^")
+            )
+    });
+    if is_operation {
+        Some(format!("({})", field))
+    } else {
+        Some(field)
+    }
+}
+
 /// Converts a MapLibre filter expression into a human-readable legend label.
 ///
-/// Supports `has`, `!` (not-has), and comparison operators (`==`, `!=`, `>`, `>=`, `<`, `<=`).
-/// Unknown operators fall back to the string `"cond"`.
-pub fn format_condition(cond: &serde_json::Value) -> Result<String, LegendError> {
+/// Supports both expression and legacy filter syntax:
+/// - `all` / `any` / `none`: sub-conditions joined with "and" / "or" (nested groups are
+///   parenthesized).
+/// - `!` around any condition: `without field` for `has`, negated membership for `in`,
+///   `!=` for `==`, and `not (...)` otherwise. Legacy `!has` and `!in` are handled too.
+/// - Comparisons (`==`, `!=`, `>`, `>=`, `<`, `<=`), e.g. `tipo == bosque`.
+/// - `in` with a literal array (`class in a, b`), a substring test (`name contains x`), or the
+///   legacy list form `["in", "class", "a", "b"]`.
+/// - Boolean `match`, e.g. `["match", ["get", "class"], ["a", "b"], true, false]`.
+/// - `geometry-type` / `$type` (shown as "geometry type") and `$id` (shown as "id").
+///
+/// Words are in the language of [`LegendConfig::locale`] (see [`words`]). Returns `None`
+/// for conditions with unknown operators or undescribable inputs (e.g. `["zoom"]`), also
+/// when they are one member of an `all` / `any` group, and for groups without members.
+pub fn format_condition(
+    cond: &serde_json::Value,
+    config: &LegendConfig,
+) -> Result<Option<String>, LegendError> {
+    describe_condition(cond, words(config.locale.as_deref()))
+}
+
+/// Implements [`format_condition`] with the given words.
+fn describe_condition(cond: &Value, words: &Words) -> Result<Option<String>, LegendError> {
     let arr = cond.as_array().ok_or_else(|| {
         LegendError::InvalidExpression("The condition is not an array".to_string())
     })?;
     if arr.is_empty() {
-        return Ok("cond".to_string());
+        return Ok(None);
     }
 
     let op = arr[0].as_str().ok_or_else(|| {
@@ -419,24 +921,56 @@ pub fn format_condition(cond: &serde_json::Value) -> Result<String, LegendError>
     })?;
 
     match op {
+        "all" | "any" | "none" => {
+            if arr.len() < 2 {
+                return Ok(None);
+            }
+            let joiner = if op == "all" { words.and } else { words.or };
+            let parts = arr[1..]
+                .iter()
+                .map(|cond| format_operand(cond, words))
+                .collect::<Result<Option<Vec<_>>, _>>()?;
+            let Some(parts) = parts else {
+                return Ok(None);
+            };
+            let joined = parts.join(&format!(" {} ", joiner));
+            if op == "none" {
+                Ok(Some(format!("{} ({})", words.not, joined)))
+            } else {
+                Ok(Some(joined))
+            }
+        }
         "!" => {
-            if let Some(inner) = arr.get(1)
-                && let Some(inner_arr) = inner.as_array()
-                && inner_arr.first() == Some(&serde_json::Value::String("has".into()))
-                && let Some(field) = inner_arr.get(1).and_then(|v| v.as_str())
-            {
-                return Ok(format!("without {}", field));
+            let inner = arr.get(1).ok_or_else(|| {
+                LegendError::InvalidExpression("Missing operand in '!' expression".to_string())
+            })?;
+            let inner_op = inner
+                .as_array()
+                .and_then(|a| a.first())
+                .and_then(|v| v.as_str());
+            match inner_op {
+                Some("has") => describe_condition(&negate_legacy(inner, "!has"), words),
+                Some("in") => describe_condition(&negate_legacy(inner, "!in"), words),
+                Some("==") => describe_condition(&negate_legacy(inner, "!="), words),
+                _ => Ok(describe_condition(inner, words)
+                    .ok()
+                    .flatten()
+                    .map(|inner| format!("{} ({})", words.not, inner))),
             }
-
-            Ok("undefined".to_string())
         }
-        "has" => {
+        "has" | "!has" => {
             if let Some(field) = arr.get(1).and_then(|v| v.as_str()) {
-                Ok(format!("has {}", field))
+                let verb = if op == "has" {
+                    words.has
+                } else {
+                    words.without
+                };
+                Ok(Some(format!("{} {}", verb, field)))
             } else {
-                Err(LegendError::InvalidExpression(
-                    "Missing field in 'has' expression".to_string(),
-                ))
+                Err(LegendError::InvalidExpression(format!(
+                    "Missing field in '{}' expression",
+                    op
+                )))
             }
         }
         "==" | "!=" | ">" | ">=" | "<" | "<=" => {
@@ -445,35 +979,159 @@ pub fn format_condition(cond: &serde_json::Value) -> Result<String, LegendError>
                     "Comparison expression requires at least three elements".to_string(),
                 ));
             }
-            let field_expr = &arr[1];
-            let field = extract_field(field_expr).map_err(|e| {
-                LegendError::InvalidExpression(format!(
-                    "Invalid field expression in comparison: {}",
-                    e
-                ))
-            })?;
-            let value = match &arr[2] {
-                serde_json::Value::String(s) => s.clone(),
-                serde_json::Value::Number(n) => n.to_string(),
-                serde_json::Value::Bool(b) => b.to_string(),
-                _ => {
-                    return Err(LegendError::InvalidExpression(
-                        "Invalid value in comparison".to_string(),
-                    ));
-                }
+            // Inputs other than feature properties (e.g. `["zoom"]`) are not described.
+            let (Ok(field), Some(value)) = (condition_field(&arr[1], words), output_value(&arr[2]))
+            else {
+                return Ok(None);
+            };
+            Ok(Some(format!("{} {} {}", field, op, value)))
+        }
+        "in" | "!in" => format_in(arr, op == "!in", words).map(Some),
+        "match" => format_boolean_match(arr, words),
+        "boolean" | "to-boolean" => match arr.get(1) {
+            Some(inner) if inner.is_array() => describe_condition(inner, words),
+            _ => Ok(None),
+        },
+        _ => Ok(None),
+    }
+}
+
+/// Formats a sub-condition of `all` / `any` / `none`, parenthesizing nested groups.
+///
+/// A sub-condition that cannot be described, malformed ones included, gives `None` so
+/// that the whole group does.
+fn format_operand(cond: &Value, words: &Words) -> Result<Option<String>, LegendError> {
+    let Ok(Some(label)) = describe_condition(cond, words) else {
+        return Ok(None);
+    };
+    // A group with a single member prints without a joiner, so it needs no parentheses.
+    let is_group = cond.as_array().is_some_and(|a| {
+        a.len() > 2 && matches!(a.first().and_then(|v| v.as_str()), Some("all" | "any"))
+    });
+    if is_group {
+        Ok(Some(format!("({})", label)))
+    } else {
+        Ok(Some(label))
+    }
+}
+
+/// Replaces the operator of `cond` with `negated_op`, keeping its arguments.
+fn negate_legacy(cond: &Value, negated_op: &str) -> Value {
+    let mut arr = cond.as_array().cloned().unwrap_or_default();
+    if let Some(op) = arr.first_mut() {
+        *op = Value::String(negated_op.to_string());
+    }
+    Value::Array(arr)
+}
+
+/// Returns the field name for the left-hand side of a condition.
+///
+/// Accepts expression inputs (see [`extract_field`]) and legacy filter keys, where
+/// `$type` means the geometry type and `$id` the feature id.
+fn condition_field<'a>(value: &'a Value, words: &Words) -> Result<&'a str, LegendError> {
+    match value.as_str() {
+        Some("$type") => Ok(words.geometry_type),
+        Some("$id") => Ok("id"),
+        Some(field) => Ok(field),
+        None => extract_field(value),
+    }
+}
+
+/// Formats `field in values` (or its negation) with a single value shown as `==` / `!=`.
+fn format_membership(field: &str, values: &[String], negated: bool, words: &Words) -> String {
+    match (values, negated) {
+        ([value], false) => format!("{} == {}", field, value),
+        ([value], true) => format!("{} != {}", field, value),
+        (_, false) => format!("{} {} {}", field, words.is_in, values.join(", ")),
+        (_, true) => format!("{} {} {}", field, words.not_in, values.join(", ")),
+    }
+}
+
+/// Converts literal values (or a `["literal", [...]]` array) into label strings.
+fn literal_values(values: &[Value]) -> Result<Vec<String>, LegendError> {
+    let mut result = Vec::new();
+    for value in values {
+        match eval_constant(value) {
+            Some(Value::Array(items)) => result.extend(literal_values(&items)?),
+            Some(other) => result.push(output_value(&other).ok_or_else(|| {
+                LegendError::InvalidExpression("Invalid value in 'in' expression".to_string())
+            })?),
+            None => {
+                return Err(LegendError::InvalidExpression(
+                    "Invalid value in 'in' expression".to_string(),
+                ));
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Formats `in` / `!in` in both expression syntax (`["in", input, haystack]`) and
+/// legacy syntax (`["in", "field", v1, v2, ...]`).
+fn format_in(arr: &[Value], negated: bool, words: &Words) -> Result<String, LegendError> {
+    if arr.len() < 3 {
+        return Err(LegendError::InvalidExpression(
+            "'in' expression requires at least three elements".to_string(),
+        ));
+    }
+    let is_expression_input = |v: &Value| v.is_array() && extract_field(v).is_ok();
+    if arr.len() == 3 && is_expression_input(&arr[2]) && !is_expression_input(&arr[1]) {
+        // ["in", "needle", ["get", "field"]]: substring (or array element) test
+        let field = extract_field(&arr[2])?;
+        let needle = literal_values(&arr[1..2])?.join(", ");
+        let verb = if negated {
+            words.does_not_contain
+        } else {
+            words.contains
+        };
+        return Ok(format!("{} {} {}", field, verb, needle));
+    }
+    let field = condition_field(&arr[1], words)?;
+    let values = literal_values(&arr[2..])?;
+    Ok(format_membership(field, &values, negated, words))
+}
+
+/// Formats a `match` used as a boolean condition, e.g.
+/// `["match", ["get", "class"], ["park", "garden"], true, false]` → `class in park, garden`.
+fn format_boolean_match(arr: &[Value], words: &Words) -> Result<Option<String>, LegendError> {
+    if arr.len() < 5 || arr.len().is_multiple_of(2) {
+        return Ok(None);
+    }
+    let field = extract_field(&arr[1])?;
+    let default_true = arr[arr.len() - 1].as_bool() == Some(true);
+    let mut values = Vec::new();
+    for pair in arr[2..arr.len() - 1].chunks(2) {
+        let Some(output) = pair[1].as_bool() else {
+            return Ok(None);
+        };
+        // Collect the labels that lead to the non-default outcome.
+        if output != default_true {
+            // Match labels may be a single literal or a bare array of literals.
+            let labels = match &pair[0] {
+                Value::Array(items) => items.as_slice(),
+                label => std::slice::from_ref(label),
             };
-            Ok(format!("{} {} {}", field, op, value))
+            values.extend(literal_values(labels)?);
         }
-        _ => Ok("cond".to_string()),
     }
+    // Every value leads to the default outcome: the condition tests nothing.
+    if values.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(format_membership(field, &values, default_true, words)))
 }
 
 /// Parses a `["match", input, value, color, ..., default_color]` expression into legend entries.
 ///
 /// Each `(value, color)` pair becomes one entry. Custom labels from layer metadata are applied
-/// positionally if provided; otherwise the matched value is used as the label.
+/// positionally if provided; otherwise the matched value is used as the label, with the
+/// values of an array label joined (`["park", "garden"]` is `park, garden`).
 /// The final element is the default color, paired with the layer's `default` metadata label.
-fn parse_match(layer: &Layer, arr: &[Value]) -> Result<Vec<(String, String)>, LegendError> {
+fn parse_match(
+    layer: &Layer,
+    config: &LegendConfig,
+    arr: &[Value],
+) -> Result<Vec<(String, String)>, LegendError> {
     if arr.len() < 4 {
         return Err(LegendError::InvalidExpression(format!(
             "Layer '{}': 'match' expression too short (need at least 4 elements)",
@@ -481,14 +1139,14 @@ fn parse_match(layer: &Layer, arr: &[Value]) -> Result<Vec<(String, String)>, Le
         )));
     }
 
-    let _field = extract_field(&arr[1]).map_err(|e| {
+    let field = extract_field(&arr[1]).map_err(|e| {
         LegendError::InvalidExpression(format!(
             "Layer '{}': invalid input expression in 'match': {}",
             layer.id, e
         ))
     })?;
 
-    let labels = get_custom_labels(layer)?;
+    let labels = get_custom_labels(layer, config)?;
     let mut result = Vec::new();
     let mut i = 2;
     let mut label_index = 0;
@@ -497,23 +1155,15 @@ fn parse_match(layer: &Layer, arr: &[Value]) -> Result<Vec<(String, String)>, Le
         let value = arr.get(i).ok_or_else(|| {
             LegendError::InvalidExpression("Missing value in 'match' expression".to_string())
         })?;
-        let value_str = match value {
-            Value::String(s) => s.clone(),
-            Value::Number(n) => n.to_string(),
-            _ => {
-                return Err(LegendError::InvalidExpression(
-                    "Value is not a string or number in 'match'".to_string(),
-                ));
-            }
-        };
-        let color = arr
-            .get(i + 1)
-            .ok_or_else(|| {
-                LegendError::InvalidExpression("Missing color in 'match' expression".to_string())
-            })?
-            .as_str()
-            .unwrap_or(FALLBACK_COLOR)
-            .to_string();
+        let value_str = match_label(field, value).ok_or_else(|| {
+            LegendError::InvalidExpression(
+                "Value is not a string, number, boolean or array of them in 'match'".to_string(),
+            )
+        })?;
+        let color = arr.get(i + 1).ok_or_else(|| {
+            LegendError::InvalidExpression("Missing color in 'match' expression".to_string())
+        })?;
+        let color = output_value(color).unwrap_or_else(|| FALLBACK_COLOR.to_string());
         let label = if !labels.is_empty() && label_index < labels.len() {
             labels[label_index].clone()
         } else {
@@ -524,24 +1174,55 @@ fn parse_match(layer: &Layer, arr: &[Value]) -> Result<Vec<(String, String)>, Le
         label_index += 1;
     }
 
-    if let Some(default_color) = arr.last().and_then(|v| v.as_str()) {
+    if let Some(default_color) = arr.last().and_then(output_value) {
         let default_label = if !labels.is_empty() && label_index < labels.len() {
             labels[label_index].clone()
         } else {
-            get_layer_default_label(layer)?
+            get_layer_default_label(layer, config)?
         };
-        result.push((default_label, default_color.to_string()));
+        result.push((default_label, default_color));
     }
 
     Ok(result)
 }
 
+/// Returns the text of a `match` label over `field`: a string or number, or an array of
+/// them joined with commas. A boolean reads as a comparison, e.g. `public == true`, as in
+/// the `case` labels of a boolean `match`.
+///
+/// MapLibre only allows string and number labels in `match`; booleans are accepted
+/// leniently, as some styles use them.
+pub fn match_label(field: &str, value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(format!("{} == {}", field, b)),
+        Value::Array(values) if !values.is_empty() => {
+            let labels = values
+                .iter()
+                .map(|v| match v {
+                    Value::String(s) => Some(s.clone()),
+                    Value::Number(n) => Some(n.to_string()),
+                    Value::Bool(b) => Some(b.to_string()),
+                    _ => None,
+                })
+                .collect::<Option<Vec<String>>>()?;
+            Some(labels.join(", "))
+        }
+        _ => None,
+    }
+}
+
 /// Parses a `["case", cond1, color1, cond2, color2, ..., default_color]` expression.
 ///
 /// Each condition is converted to a human-readable string via [`format_condition`].
 /// Custom labels replace conditions when provided in layer metadata.
-fn parse_case(layer: &Layer, arr: &[Value]) -> Result<Vec<(String, String)>, LegendError> {
-    let labels = get_custom_labels(layer)?;
+fn parse_case(
+    layer: &Layer,
+    config: &LegendConfig,
+    arr: &[Value],
+) -> Result<Vec<(String, String)>, LegendError> {
+    let labels = get_custom_labels(layer, config)?;
     let mut result = Vec::new();
     let mut i = 1;
     let mut label_index = 0;
@@ -550,18 +1231,15 @@ fn parse_case(layer: &Layer, arr: &[Value]) -> Result<Vec<(String, String)>, Leg
         let cond = arr.get(i).ok_or_else(|| {
             LegendError::InvalidExpression("Missing condition in 'case' expression".to_string())
         })?;
-        let color = arr
-            .get(i + 1)
-            .ok_or_else(|| {
-                LegendError::InvalidExpression("Missing color in 'case' expression".to_string())
-            })?
-            .as_str()
-            .unwrap_or(FALLBACK_COLOR)
-            .to_string();
+        let color = arr.get(i + 1).ok_or_else(|| {
+            LegendError::InvalidExpression("Missing color in 'case' expression".to_string())
+        })?;
+        let color = output_value(color).unwrap_or_else(|| FALLBACK_COLOR.to_string());
         let label = if !labels.is_empty() && label_index < labels.len() {
             labels[label_index].clone()
         } else {
-            format_condition(cond)?
+            // Conditions that cannot be described keep a generic label.
+            format_condition(cond, config)?.unwrap_or_else(|| "cond".to_string())
         };
         result.push((label, color));
         label_index += 1;
@@ -569,94 +1247,80 @@ fn parse_case(layer: &Layer, arr: &[Value]) -> Result<Vec<(String, String)>, Leg
     }
 
     if arr.len().is_multiple_of(2)
-        && let Some(default_color) = arr.last().and_then(|v| v.as_str())
+        && let Some(default_color) = arr.last().and_then(output_value)
     {
         let default_label = if !labels.is_empty() && label_index < labels.len() {
             labels[label_index].clone()
         } else {
-            get_layer_default_label(layer)?
+            get_layer_default_label(layer, config)?
         };
-        result.push((default_label, default_color.to_string()));
+        result.push((default_label, default_color));
     }
 
     Ok(result)
 }
 
-/// Parses a `["interpolate", interp, ["get", field], stop0, color0, stop1, color1, ...]` expression.
+/// Parses a `["interpolate", interp, input, stop0, color0, stop1, color1, ...]` expression.
+/// The input is named in labels as described by [`numeric_input_field`].
 ///
-/// Each `(stop, color)` pair becomes a legend entry labelled `"field ≥ stop"`.
-/// Custom labels from layer metadata are applied positionally when provided.
-fn parse_interpolate(layer: &Layer, arr: &[Value]) -> Result<Vec<(String, String)>, LegendError> {
+/// Each `(stop, color)` pair becomes a legend entry labelled `"field ≥ stop"`, or as chosen
+/// by the layer's `interpolate-labels` option (see [`crate::numbers::StopLabels`]), written
+/// with the layer's [`NumberFormat`]. Custom labels from layer metadata are applied
+/// positionally when provided.
+fn parse_interpolate(
+    layer: &Layer,
+    config: &LegendConfig,
+    arr: &[Value],
+) -> Result<Vec<(String, String)>, LegendError> {
     if arr.len() < 4 {
         return Err(LegendError::InvalidExpression(format!(
             "Layer '{}': 'interpolate' expression too short (need at least 4 elements)",
             layer.id
         )));
     }
-    let labels = get_custom_labels(layer)?;
+    let labels = get_custom_labels(layer, config)?;
 
-    let field = arr
-        .get(2)
-        .and_then(|v| {
-            if let Some(get_arr) = v.as_array()
-                && get_arr.len() == 2
-                && get_arr[0] == "get"
-            {
-                return get_arr[1].as_str();
-            }
+    let field = arr.get(2).and_then(numeric_input_field).ok_or_else(|| {
+        LegendError::InvalidExpression("Unsupported input in 'interpolate' expression".to_string())
+    })?;
+    let format = NumberFormat::for_layer(layer, config)?;
 
-            None
+    let pairs: Vec<&[Value]> = arr[3..].chunks_exact(2).collect();
+    let stops = pairs
+        .iter()
+        .map(|pair| {
+            pair[0].as_f64().ok_or_else(|| {
+                LegendError::InvalidExpression("Value is not a number in 'interpolate'".to_string())
+            })
         })
-        .ok_or_else(|| {
-            LegendError::InvalidExpression(
-                "Invalid 'get' field in 'interpolate' expression".to_string(),
-            )
-        })?;
+        .collect::<Result<Vec<f64>, LegendError>>()?;
 
     let mut result = Vec::new();
-    let mut i = 3;
-    let mut label_index = 0;
-    while i + 1 < arr.len() {
-        let val = arr
-            .get(i)
-            .ok_or_else(|| {
-                LegendError::InvalidExpression(
-                    "Missing value in 'interpolate' expression".to_string(),
-                )
-            })?
-            .as_f64()
-            .ok_or_else(|| {
-                LegendError::InvalidExpression("Value is not a number in 'interpolate'".to_string())
-            })?;
-        let color = arr
-            .get(i + 1)
-            .ok_or_else(|| {
-                LegendError::InvalidExpression(
-                    "Missing color in 'interpolate' expression".to_string(),
-                )
-            })?
-            .as_str()
-            .unwrap_or(FALLBACK_COLOR)
-            .to_string();
+    for (label_index, pair) in pairs.iter().enumerate() {
+        let color = output_value(&pair[1]).unwrap_or_else(|| FALLBACK_COLOR.to_string());
         let label = if !labels.is_empty() && label_index < labels.len() {
             labels[label_index].clone()
         } else {
-            format!("{field} ≥ {val}")
+            format.stop(&field, &stops, label_index)
         };
         result.push((label, color));
-        i += 2;
-        label_index += 1;
     }
 
     Ok(result)
 }
 
-/// Parses a `["step", ["get", field], base_color, threshold1, color1, ...]` expression.
+/// Parses a `["step", input, base_color, threshold1, color1, ...]` expression. The input is
+/// named in labels as described by [`numeric_input_field`], e.g. `pop / area`.
 ///
 /// The base entry is labelled `"field < threshold1"` (or the layer label if there are no thresholds).
-/// Each subsequent `(threshold, color)` pair is labelled `"t ≤ field < next_t"`, or `"field ≥ t"` for
-/// the last one. Custom labels from layer metadata are applied positionally when provided.
-fn parse_step(layer: &Layer, arr: &[Value]) -> Result<Vec<(String, String)>, LegendError> {
+/// Each subsequent `(threshold, color)` pair is labelled `"t ≤ field < next_t"` (or the layer's
+/// `range-template`), or `"field ≥ t"` for the last one, written with the layer's
+/// [`NumberFormat`]. Custom labels from layer metadata are applied positionally when provided.
+fn parse_step(
+    layer: &Layer,
+    config: &LegendConfig,
+    arr: &[Value],
+) -> Result<Vec<(String, String)>, LegendError> {
     // Minimum length: ["step", input, base_output]
     if arr.len() < 3 {
         return Err(LegendError::InvalidExpression(format!(
@@ -673,53 +1337,38 @@ fn parse_step(layer: &Layer, arr: &[Value]) -> Result<Vec<(String, String)>, Leg
         )));
     }
 
-    let labels = get_custom_labels(layer)?;
-
-    // Extract the field from input expression (e.g., ["get", "cantidad"])
-    let field = arr
-        .get(1)
-        .and_then(|v| {
-            if let Some(get_arr) = v.as_array()
-                && get_arr.len() == 2
-                && get_arr[0] == "get"
-            {
-                return get_arr[1].as_str();
-            }
+    let labels = get_custom_labels(layer, config)?;
 
-            None
-        })
-        .ok_or_else(|| {
-            LegendError::InvalidExpression("Invalid 'get' field in 'step' expression".to_string())
-        })?;
+    // Describe the input expression (e.g., ["get", "cantidad"] as "cantidad")
+    let field = arr.get(1).and_then(numeric_input_field).ok_or_else(|| {
+        LegendError::InvalidExpression("Unsupported input in 'step' expression".to_string())
+    })?;
+    let format = NumberFormat::for_layer(layer, config)?;
 
     let mut result = Vec::new();
     let mut label_index = 0;
 
     // Handle base output (for values below the first threshold, if any)
-    let base_color = arr
-        .get(2)
-        .ok_or_else(|| {
-            LegendError::InvalidExpression("Missing base color in 'step' expression".to_string())
-        })?
-        .as_str()
-        .ok_or_else(|| {
-            LegendError::InvalidExpression("Base color is not a string in 'step'".to_string())
-        })?
-        .to_string();
+    let base_color = arr.get(2).ok_or_else(|| {
+        LegendError::InvalidExpression("Missing base color in 'step' expression".to_string())
+    })?;
+    let base_color = output_value(base_color).ok_or_else(|| {
+        LegendError::InvalidExpression("Base color is not a string in 'step'".to_string())
+    })?;
 
     let base_label = if arr.len() == 3 {
         // If no thresholds, use layer label or default
         if !labels.is_empty() && label_index < labels.len() {
             labels[label_index].clone()
         } else {
-            get_layer_label(layer)?
+            get_layer_label(layer, config)?
         }
     } else {
         // Use first threshold for base label
         if !labels.is_empty() && label_index < labels.len() {
             labels[label_index].clone()
         } else {
-            format!("{} < {}", field, arr[3].as_f64().unwrap_or(0.0))
+            format.below(&field, arr[3].as_f64().unwrap_or(0.0))
         }
     };
     result.push((base_label, base_color));
@@ -737,16 +1386,12 @@ fn parse_step(layer: &Layer, arr: &[Value]) -> Result<Vec<(String, String)>, Leg
             .ok_or_else(|| {
                 LegendError::InvalidExpression("Threshold is not a number in 'step'".to_string())
             })?;
-        let color = arr
-            .get(i + 1)
-            .ok_or_else(|| {
-                LegendError::InvalidExpression("Missing color in 'step' expression".to_string())
-            })?
-            .as_str()
-            .ok_or_else(|| {
-                LegendError::InvalidExpression("Color is not a string in 'step'".to_string())
-            })?
-            .to_string();
+        let color = arr.get(i + 1).ok_or_else(|| {
+            LegendError::InvalidExpression("Missing color in 'step' expression".to_string())
+        })?;
+        let color = output_value(color).ok_or_else(|| {
+            LegendError::InvalidExpression("Color is not a string in 'step'".to_string())
+        })?;
 
         let next_threshold = if i + 2 < arr.len() {
             arr[i + 2].as_f64()
@@ -757,9 +1402,9 @@ fn parse_step(layer: &Layer, arr: &[Value]) -> Result<Vec<(String, String)>, Leg
         let label = if !labels.is_empty() && label_index < labels.len() {
             labels[label_index].clone()
         } else if let Some(next) = next_threshold {
-            format!("{} ≤ {} < {}", threshold, field, next)
+            format.range(&field, threshold, next)
         } else {
-            format!("{} ≥ {}", field, threshold)
+            format.at_least(&field, threshold)
         };
 
         result.push((label, color));
@@ -774,50 +1419,168 @@ fn parse_step(layer: &Layer, arr: &[Value]) -> Result<Vec<(String, String)>, Leg
 /// Returns entries from the first argument that parses as a multi-value expression
 /// (match / case / interpolate / step). If none is found, returns the last string
 /// argument as a single-color fallback entry.
-fn parse_coalesce(layer: &Layer, arr: &[Value]) -> Result<Vec<(String, String)>, LegendError> {
+fn parse_coalesce(
+    layer: &Layer,
+    config: &LegendConfig,
+    arr: &[Value],
+) -> Result<Vec<(String, String)>, LegendError> {
     for arg in arr.iter().skip(1) {
         if let Some(arg_arr) = arg.as_array()
             && let Some(op) = arg_arr.first().and_then(|v| v.as_str())
             && matches!(op, "match" | "case" | "interpolate" | "step")
-            && let Ok(entries) = parse_expression(layer, arg)
+            && let Ok(entries) = parse_expression(layer, config, arg)
             && entries.len() > 1
         {
             return Ok(entries);
         }
     }
-    // Fallback: use last string value as the color
+    // Fallback: use last literal value as the color
     let fallback_color = arr
         .iter()
+        .skip(1)
         .rev()
-        .find_map(|v| v.as_str())
-        .unwrap_or(FALLBACK_COLOR)
-        .to_string();
-    let label = get_layer_label(layer)?;
+        .find_map(output_value)
+        .unwrap_or_else(|| FALLBACK_COLOR.to_string());
+    let label = get_layer_label(layer, config)?;
     Ok(vec![(label, fallback_color)])
 }
 
+/// Evaluates a constant color constructor (`rgb`, `rgba`, `to-color`) into a single entry.
+///
+/// Constructors that depend on feature data (e.g. `["to-color", ["get", "color"]]`) use
+/// [`FALLBACK_COLOR`].
+fn parse_color_constructor(
+    layer: &Layer,
+    config: &LegendConfig,
+    value: &Value,
+) -> Result<Vec<(String, String)>, LegendError> {
+    let label = get_layer_label(layer, config)?;
+    let color = output_value(value).unwrap_or_else(|| FALLBACK_COLOR.to_string());
+    Ok(vec![(label, color)])
+}
+
 /// Treats `["literal", value]` as a plain color value.
-fn parse_literal(layer: &Layer, arr: &[Value]) -> Result<Vec<(String, String)>, LegendError> {
+fn parse_literal(
+    layer: &Layer,
+    config: &LegendConfig,
+    arr: &[Value],
+) -> Result<Vec<(String, String)>, LegendError> {
     if arr.len() < 2 {
         return Err(LegendError::InvalidExpression(
             "'literal' requires a value argument".to_string(),
         ));
     }
-    let label = get_layer_label(layer)?;
-    let color = arr[1].as_str().unwrap_or(FALLBACK_COLOR).to_string();
+    let label = get_layer_label(layer, config)?;
+    let color = output_value(&arr[1]).unwrap_or_else(|| FALLBACK_COLOR.to_string());
     Ok(vec![(label, color)])
 }
 
+/// Converts a legacy style function (`{"property": ..., "type": ..., "stops": [...]}`) into
+/// the equivalent expression, so it can be parsed like any other paint value.
+///
+/// - `categorical` → `match` (the function `default` becomes the match fallback, if any).
+/// - `interval` → `step`, with the first stop's output as the base.
+/// - `exponential` → `interpolate` (`linear` when `base` is 1 or absent).
+/// - `identity` → `coalesce` over the property with the function `default`.
+///
+/// Functions without `property` are zoom functions and use `["zoom"]` as input. Composite
+/// functions (`{"zoom": z, "value": v}` stop keys) are keyed by their `value`. When `type` is
+/// absent, it defaults to `exponential` for colors and numbers and `interval` otherwise,
+/// as in the style specification.
+fn legacy_function_to_expression(
+    layer: &Layer,
+    obj: &Map<String, Value>,
+) -> Result<Value, LegendError> {
+    let input = match obj.get("property").and_then(|p| p.as_str()) {
+        Some(property) => Value::from(vec!["get", property]),
+        None => Value::from(vec!["zoom"]),
+    };
+    let default = obj.get("default").cloned().unwrap_or(Value::Null);
+    let function_type = obj.get("type").and_then(|t| t.as_str());
+
+    if function_type == Some("identity") {
+        return Ok(Value::Array(vec!["coalesce".into(), input, default]));
+    }
+
+    let invalid = |msg: &str| {
+        LegendError::InvalidExpression(format!("Layer '{}': {} in legacy function", layer.id, msg))
+    };
+    let mut stops: Vec<(Value, Value)> = Vec::new();
+    for stop in obj
+        .get("stops")
+        .and_then(|s| s.as_array())
+        .ok_or_else(|| invalid("missing 'stops'"))?
+    {
+        let (key, output) = match stop.as_array().map(|s| s.as_slice()) {
+            Some([key, output]) => (key, output),
+            _ => return Err(invalid("stop is not a [input, output] pair")),
+        };
+        let key = key.get("value").unwrap_or(key).clone();
+        if !stops.iter().any(|(k, _)| *k == key) {
+            stops.push((key, output.clone()));
+        }
+    }
+    if stops.is_empty() {
+        return Err(invalid("empty 'stops'"));
+    }
+
+    let interpolatable = stops.iter().all(|(_, output)| match output {
+        Value::Number(_) => true,
+        Value::String(s) => Color::parse(s).is_some(),
+        _ => false,
+    });
+    let function_type = function_type.unwrap_or(if interpolatable {
+        "exponential"
+    } else {
+        "interval"
+    });
+
+    let mut expr: Vec<Value> = match function_type {
+        "categorical" => vec!["match".into(), input],
+        "interval" => vec!["step".into(), input, stops[0].1.clone()],
+        "exponential" => {
+            let base = obj.get("base").and_then(|b| b.as_f64()).unwrap_or(1.0);
+            let interpolation = if base == 1.0 {
+                Value::from(vec!["linear"])
+            } else {
+                Value::Array(vec!["exponential".into(), base.into()])
+            };
+            vec!["interpolate".into(), interpolation, input]
+        }
+        other => return Err(invalid(&format!("unsupported type '{}'", other))),
+    };
+    let pairs = if function_type == "interval" {
+        &stops[1..]
+    } else {
+        &stops[..]
+    };
+    for (key, output) in pairs {
+        expr.push(key.clone());
+        expr.push(output.clone());
+    }
+    if function_type == "categorical" {
+        expr.push(default);
+    }
+    Ok(Value::Array(expr))
+}
+
 /// Parses a MapLibre paint value into a list of `(label, color)` legend entries.
 ///
 /// - Plain strings, numbers, and booleans produce a single entry using the layer label.
 /// - Arrays are dispatched by operator: `match`, `case`, `interpolate`, `step`, `coalesce`, `literal`.
+/// - Objects are legacy style functions and are converted with [`legacy_function_to_expression`].
+/// - `let` bindings are inlined before dispatching on the body; `rgb`, `rgba` and `to-color`
+///   produce a single entry with the evaluated color.
 ///
 /// Returns [`LegendError::InvalidExpression`] for unsupported or malformed expressions.
 pub fn parse_expression(
     layer: &Layer,
+    config: &LegendConfig,
     value: &serde_json::Value,
 ) -> Result<Vec<(String, String)>, LegendError> {
+    if let Some(obj) = value.as_object() {
+        return parse_expression(layer, config, &legacy_function_to_expression(layer, obj)?);
+    }
     if !value.is_array() {
         let value_str = match value {
             serde_json::Value::String(s) => s.clone(),
@@ -830,13 +1593,14 @@ pub fn parse_expression(
                 )));
             }
         };
-        let label = get_layer_label(layer)?;
+        let label = get_layer_label(layer, config)?;
         return Ok(vec![(label, value_str)]);
     }
 
     let arr = value.as_array().ok_or_else(|| {
         LegendError::InvalidExpression(format!("The value is not an array. Layer: {}", layer.id))
     })?;
+    check_let(value)?;
     let first = arr
         .first()
         .ok_or_else(|| {
@@ -854,12 +1618,14 @@ pub fn parse_expression(
         })?;
 
     match ExpressionKind::from_str(first)? {
-        ExpressionKind::Match => parse_match(layer, arr),
-        ExpressionKind::Case => parse_case(layer, arr),
-        ExpressionKind::Interpolate => parse_interpolate(layer, arr),
-        ExpressionKind::Step => parse_step(layer, arr),
-        ExpressionKind::Coalesce => parse_coalesce(layer, arr),
-        ExpressionKind::Literal => parse_literal(layer, arr),
+        ExpressionKind::Match => parse_match(layer, config, arr),
+        ExpressionKind::Case => parse_case(layer, config, arr),
+        ExpressionKind::Interpolate => parse_interpolate(layer, config, arr),
+        ExpressionKind::Step => parse_step(layer, config, arr),
+        ExpressionKind::Coalesce => parse_coalesce(layer, config, arr),
+        ExpressionKind::Literal => parse_literal(layer, config, arr),
+        ExpressionKind::Let => parse_expression(layer, config, &inline_let(value)?),
+        ExpressionKind::Color => parse_color_constructor(layer, config, value),
     }
 }
 
@@ -907,6 +1673,7 @@ mod tests {
         let layer: Layer = serde_json::from_value(layer).unwrap();
         let result = parse_match(
             &layer.clone(),
+            &LegendConfig::default(),
             layer.paint.unwrap()["fill-color"].as_array().unwrap(),
         );
         assert_eq!(
@@ -937,7 +1704,7 @@ mod tests {
             ],
             "#aaaaaa"
         ]);
-        let result = parse_expression(&layer, &expr).unwrap();
+        let result = parse_expression(&layer, &LegendConfig::default(), &expr).unwrap();
         assert_eq!(result.len(), 3);
         assert_eq!(result[0], ("bosque".to_string(), "#228B22".to_string()));
         assert_eq!(result[1], ("agua".to_string(), "#4169E1".to_string()));
@@ -950,7 +1717,7 @@ mod tests {
 
         // coalesce with only a get + fallback color — should return fallback
         let expr = json!(["coalesce", ["get", "color"], "#ff0000"]);
-        let result = parse_expression(&layer, &expr).unwrap();
+        let result = parse_expression(&layer, &LegendConfig::default(), &expr).unwrap();
         assert_eq!(result.len(), 1);
         assert_eq!(result[0].1, "#ff0000");
     }
@@ -961,7 +1728,7 @@ mod tests {
         let layer: Layer = serde_json::from_value(layer).unwrap();
 
         let expr = json!(["literal", "#ff0000"]);
-        let result = parse_expression(&layer, &expr).unwrap();
+        let result = parse_expression(&layer, &LegendConfig::default(), &expr).unwrap();
         assert_eq!(result.len(), 1);
         assert_eq!(result[0].1, "#ff0000");
     }
@@ -977,7 +1744,7 @@ mod tests {
             "#00ff00",
             "#cccccc"
         ]);
-        let result = parse_expression(&layer, &expr).unwrap();
+        let result = parse_expression(&layer, &LegendConfig::default(), &expr).unwrap();
         assert_eq!(result.len(), 3);
         assert_eq!(result[0].0, "has nombre");
         assert_eq!(result[0].1, "#ff0000");
@@ -1000,7 +1767,7 @@ mod tests {
             "#00ff00",
             "#0000ff"
         ]);
-        let result = parse_expression(&layer, &expr).unwrap();
+        let result = parse_expression(&layer, &LegendConfig::default(), &expr).unwrap();
         assert_eq!(result[0].0, "Label A");
         assert_eq!(result[1].0, "Label B");
         assert_eq!(result[2].0, "Otros");
@@ -1020,7 +1787,7 @@ mod tests {
             100,
             "#00ff00"
         ]);
-        let result = parse_expression(&layer, &expr).unwrap();
+        let result = parse_expression(&layer, &LegendConfig::default(), &expr).unwrap();
         assert_eq!(result.len(), 3);
         assert_eq!(result[0].0, "value ≥ 0");
         assert_eq!(result[0].1, "#ff0000");
@@ -1046,7 +1813,7 @@ mod tests {
             1000,
             "#800026"
         ]);
-        let result = parse_expression(&layer, &expr).unwrap();
+        let result = parse_expression(&layer, &LegendConfig::default(), &expr).unwrap();
         assert_eq!(result[0].0, "Bajo");
         assert_eq!(result[1].0, "Medio");
         assert_eq!(result[2].0, "Alto");
@@ -1066,7 +1833,7 @@ mod tests {
             100,
             "#e31a1c"
         ]);
-        let result = parse_expression(&layer, &expr).unwrap();
+        let result = parse_expression(&layer, &LegendConfig::default(), &expr).unwrap();
         assert_eq!(result.len(), 4);
         assert_eq!(result[0].0, "count < 10");
         assert_eq!(result[0].1, "#ffffb2");
@@ -1074,11 +1841,211 @@ mod tests {
         assert_eq!(result[3].0, "count ≥ 100");
     }
 
+    #[test]
+    fn test_parse_step_and_interpolate_number_format() {
+        let layer: Layer = serde_json::from_value(json!({
+            "id": "test", "type": "fill",
+            "metadata": {"legend": {
+                "decimals": 1, "unit": "USD", "grouping": true,
+                "range-template": "{min} – {max} {unit}"
+            }}
+        }))
+        .unwrap();
+        let config = LegendConfig {
+            locale: Some("es".to_string()),
+            ..Default::default()
+        };
+        let step = json!([
+            "step",
+            ["get", "precio"],
+            "#fff",
+            1000,
+            "#ccc",
+            2500.5,
+            "#000"
+        ]);
+        let labels: Vec<String> = parse_expression(&layer, &config, &step)
+            .unwrap()
+            .into_iter()
+            .map(|(label, _)| label)
+            .collect();
+        assert_eq!(
+            labels,
+            [
+                "precio < 1.000,0 USD",
+                "1.000,0 – 2.500,5 USD",
+                "precio ≥ 2.500,5 USD"
+            ]
+        );
+        let interpolate = json!([
+            "interpolate",
+            ["linear"],
+            ["get", "precio"],
+            0,
+            "#fff",
+            1500,
+            "#000"
+        ]);
+        let result = parse_expression(&layer, &config, &interpolate).unwrap();
+        assert_eq!(result[1].0, "precio ≥ 1.500,0 USD");
+    }
+
+    #[test]
+    fn test_parse_interpolate_range_labels_match_step() {
+        let layer: Layer = serde_json::from_value(json!({
+            "id": "test", "type": "fill",
+            "metadata": {"legend": {"interpolate-labels": "ranges", "unit": "m"}}
+        }))
+        .unwrap();
+        let config = LegendConfig::default();
+        let labels = |expr: Value| -> Vec<String> {
+            parse_expression(&layer, &config, &expr)
+                .unwrap()
+                .into_iter()
+                .map(|(label, _)| label)
+                .collect()
+        };
+        let interpolate = labels(json!([
+            "interpolate",
+            ["linear"],
+            ["get", "depth"],
+            0,
+            "#fff",
+            10,
+            "#888",
+            50,
+            "#000"
+        ]));
+        assert_eq!(
+            interpolate,
+            ["0 ≤ depth < 10 m", "10 ≤ depth < 50 m", "depth ≥ 50 m"]
+        );
+        let step = labels(json!([
+            "step",
+            ["get", "depth"],
+            "#fff",
+            10,
+            "#888",
+            50,
+            "#000"
+        ]));
+        assert_eq!(interpolate[1..], step[1..]);
+    }
+
+    #[test]
+    fn test_parse_match_array_and_boolean_labels() {
+        let layer: Layer = serde_json::from_value(json!({"id": "test", "type": "fill"})).unwrap();
+        let expr = json!([
+            "match",
+            ["get", "class"],
+            ["park", "garden"],
+            "#00ff00",
+            ["forest", 1],
+            "#008000",
+            "#cccccc"
+        ]);
+        let result = parse_expression(&layer, &LegendConfig::default(), &expr).unwrap();
+        assert_eq!(
+            result[0],
+            ("park, garden".to_string(), "#00ff00".to_string())
+        );
+        assert_eq!(result[1].0, "forest, 1");
+        assert_eq!(result[2].0, "test");
+
+        let expr = json!([
+            "match",
+            ["get", "public"],
+            true,
+            "#0000ff",
+            false,
+            "#ff0000",
+            "#ccc"
+        ]);
+        let labels: Vec<String> = parse_expression(&layer, &LegendConfig::default(), &expr)
+            .unwrap()
+            .into_iter()
+            .map(|(label, _)| label)
+            .collect();
+        assert_eq!(labels, ["public == true", "public == false", "test"]);
+
+        let layer: Layer = serde_json::from_value(json!({
+            "id": "test", "type": "fill",
+            "metadata": {"legend": {"custom-labels": ["Green space"]}}
+        }))
+        .unwrap();
+        let expr = json!([
+            "match",
+            ["get", "class"],
+            ["park", "garden"],
+            "#0f0",
+            "#ccc"
+        ]);
+        let result = parse_expression(&layer, &LegendConfig::default(), &expr).unwrap();
+        assert_eq!(result[0].0, "Green space");
+
+        let expr = json!(["match", ["get", "class"], [], "#0f0", "#ccc"]);
+        assert!(parse_expression(&layer, &LegendConfig::default(), &expr).is_err());
+    }
+
+    #[test]
+    fn test_numeric_input_field() {
+        let field = |input: Value| numeric_input_field(&input);
+        assert_eq!(field(json!(["get", "pop"])).as_deref(), Some("pop"));
+        assert_eq!(field(json!(["zoom"])).as_deref(), Some("zoom"));
+        assert_eq!(
+            field(json!(["feature-state", "hover-count"])).as_deref(),
+            Some("hover-count")
+        );
+        assert_eq!(
+            field(json!(["to-number", ["get", "height"], 0])).as_deref(),
+            Some("height")
+        );
+        assert_eq!(
+            field(json!(["coalesce", ["get", "pop"], 0])).as_deref(),
+            Some("pop")
+        );
+        assert_eq!(
+            field(json!(["/", ["get", "pop"], ["get", "area"]])).as_deref(),
+            Some("pop / area")
+        );
+        assert_eq!(
+            field(json!(["*", ["-", ["get", "max"], ["get", "min"]], 100])).as_deref(),
+            Some("(max - min) * 100")
+        );
+        assert_eq!(
+            field(json!(["sqrt", ["get", "area"]])).as_deref(),
+            Some("sqrt(area)")
+        );
+        assert_eq!(
+            field(json!(["let", "d", ["get", "density"], ["-", ["var", "d"]]])).as_deref(),
+            Some("-density")
+        );
+        assert_eq!(field(json!(["get"])), None);
+        assert_eq!(field(json!(["geometry-type"])), None);
+    }
+
+    #[test]
+    fn test_parse_step_arithmetic_input() {
+        let layer: Layer = serde_json::from_value(json!({"id": "test", "type": "fill"})).unwrap();
+        let expr = json!([
+            "step",
+            ["/", ["get", "pop"], ["get", "area"]],
+            "#fff",
+            100,
+            "#000"
+        ]);
+        let result = parse_expression(&layer, &LegendConfig::default(), &expr).unwrap();
+        assert_eq!(result[0].0, "pop / area < 100");
+        assert_eq!(result[1].0, "pop / area ≥ 100");
+        let expr = json!(["step", ["geometry-type"], "#fff", 100, "#000"]);
+        assert!(parse_expression(&layer, &LegendConfig::default(), &expr).is_err());
+    }
+
     #[test]
     fn test_parse_step_no_thresholds() {
         let layer: Layer = serde_json::from_value(json!({"id": "test", "type": "fill"})).unwrap();
         let expr = json!(["step", ["get", "value"], "#ff0000"]);
-        let result = parse_expression(&layer, &expr).unwrap();
+        let result = parse_expression(&layer, &LegendConfig::default(), &expr).unwrap();
         assert_eq!(result.len(), 1);
         assert_eq!(result[0].1, "#ff0000");
     }
@@ -1091,7 +2058,7 @@ mod tests {
         }))
         .unwrap();
         let expr = json!(["step", ["get", "n"], "#aaaaaa", 100, "#ff0000"]);
-        let result = parse_expression(&layer, &expr).unwrap();
+        let result = parse_expression(&layer, &LegendConfig::default(), &expr).unwrap();
         assert_eq!(result[0].0, "Bajo");
         assert_eq!(result[1].0, "Alto");
     }
@@ -1121,35 +2088,312 @@ mod tests {
     }
 
     #[test]
-    fn test_get_fill_and_opacity_non_hex() {
-        // Named colors fall through to the base opacity unchanged
+    fn test_get_fill_and_opacity_named_color() {
         let (color, opacity) = get_fill_and_opacity("red", 0.5);
-        assert_eq!(color, "red");
+        assert_eq!(color, "#ff0000");
+        assert!((opacity - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_get_fill_and_opacity_rgba_and_hsla() {
+        let (color, opacity) = get_fill_and_opacity("rgba(0, 0, 255, 0.5)", 0.8);
+        assert_eq!(color, "#0000ff");
+        assert!((opacity - 0.4).abs() < 1e-9);
+        let (color, opacity) = get_fill_and_opacity("hsla(0, 100%, 50%, 0.25)", 1.0);
+        assert_eq!(color, "#ff0000");
+        assert!((opacity - 0.25).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_get_fill_and_opacity_shorthand_hex() {
+        let (color, opacity) = get_fill_and_opacity("#F008", 1.0);
+        assert_eq!(color, "#ff0000");
+        assert!((opacity - 136.0 / 255.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_get_fill_and_opacity_invalid_passthrough() {
+        // Strings that are not colors fall through unchanged
+        let (color, opacity) = get_fill_and_opacity("not-a-color", 0.5);
+        assert_eq!(color, "not-a-color");
         assert!((opacity - 0.5).abs() < 1e-9);
     }
 
     #[test]
     fn test_format_condition_equals() {
         let cond = json!(["==", ["get", "tipo"], "bosque"]);
-        assert_eq!(format_condition(&cond).unwrap(), "tipo == bosque");
+        assert_eq!(
+            format_condition(&cond, &LegendConfig::default())
+                .unwrap()
+                .unwrap(),
+            "tipo == bosque"
+        );
+    }
+
+    #[test]
+    fn test_format_condition_all_any() {
+        let cond = json!([
+            "all",
+            ["==", ["get", "class"], "park"],
+            ["any", [">=", ["get", "area"], 100], ["has", "name"]]
+        ]);
+        assert_eq!(
+            format_condition(&cond, &LegendConfig::default())
+                .unwrap()
+                .unwrap(),
+            "class == park and (area >= 100 or has name)"
+        );
+    }
+
+    #[test]
+    fn test_format_condition_unsupported() {
+        let config = LegendConfig::default();
+        let cond = json!(["all", ["==", ["get", "x"], 1], ["within", {}]]);
+        assert_eq!(format_condition(&cond, &config).unwrap(), None);
+        assert_eq!(format_condition(&json!(["all"]), &config).unwrap(), None);
+        let cond = json!(["!", ["any", ["has", "a"], ["within", {}]]]);
+        assert_eq!(format_condition(&cond, &config).unwrap(), None);
+    }
+
+    #[test]
+    fn test_format_condition_boolean_match_without_other_outcome() {
+        let config = LegendConfig::default();
+        let cond = json!(["match", ["get", "class"], "park", true, "wood", true, true]);
+        assert_eq!(format_condition(&cond, &config).unwrap(), None);
+        let cond = json!(["match", ["get", "class"], "park", false, false]);
+        assert_eq!(format_condition(&cond, &config).unwrap(), None);
+    }
+
+    #[test]
+    fn test_parse_case_zoom_condition_in_group_is_cond() {
+        let layer: Layer = serde_json::from_value(json!({"id": "test", "type": "fill"})).unwrap();
+        let config = LegendConfig::default();
+        let expr = json!([
+            "case",
+            [
+                "all",
+                [">=", ["zoom"], 10],
+                ["==", ["get", "class"], "park"]
+            ],
+            "#00ff00",
+            [
+                "any",
+                ["==", ["geometry-type"], "Polygon"],
+                ["<", ["zoom"], 5]
+            ],
+            "#0000ff",
+            "#cccccc"
+        ]);
+        let labels: Vec<String> = parse_expression(&layer, &config, &expr)
+            .unwrap()
+            .into_iter()
+            .map(|(label, _)| label)
+            .collect();
+        assert_eq!(labels, ["cond", "cond", "test"]);
+        let cond = json!(["!", [">=", ["zoom"], 10]]);
+        assert_eq!(format_condition(&cond, &config).unwrap(), None);
+    }
+
+    #[test]
+    fn test_format_condition_not_around_expressions() {
+        assert_eq!(
+            format_condition(
+                &json!(["!", ["==", ["get", "a"], 1]]),
+                &LegendConfig::default()
+            )
+            .unwrap()
+            .unwrap(),
+            "a != 1"
+        );
+        assert_eq!(
+            format_condition(
+                &json!(["!", ["<", ["get", "a"], 1]]),
+                &LegendConfig::default()
+            )
+            .unwrap()
+            .unwrap(),
+            "not (a < 1)"
+        );
+    }
+
+    #[test]
+    fn test_format_condition_in_forms() {
+        let literal = json!(["in", ["get", "class"], ["literal", ["park", "garden"]]]);
+        assert_eq!(
+            format_condition(&literal, &LegendConfig::default())
+                .unwrap()
+                .unwrap(),
+            "class in park, garden"
+        );
+        let legacy = json!(["in", "class", "park", "garden"]);
+        assert_eq!(
+            format_condition(&legacy, &LegendConfig::default())
+                .unwrap()
+                .unwrap(),
+            "class in park, garden"
+        );
+        let legacy_not = json!(["!in", "class", "park"]);
+        assert_eq!(
+            format_condition(&legacy_not, &LegendConfig::default())
+                .unwrap()
+                .unwrap(),
+            "class != park"
+        );
+        let negated = json!(["!", ["in", ["get", "class"], ["literal", ["a", "b"]]]]);
+        assert_eq!(
+            format_condition(&negated, &LegendConfig::default())
+                .unwrap()
+                .unwrap(),
+            "class not in a, b"
+        );
+        let substring = json!(["in", "Av.", ["get", "name"]]);
+        assert_eq!(
+            format_condition(&substring, &LegendConfig::default())
+                .unwrap()
+                .unwrap(),
+            "name contains Av."
+        );
+    }
+
+    #[test]
+    fn test_format_condition_boolean_match() {
+        let cond = json!(["match", ["get", "class"], ["park", "garden"], true, false]);
+        assert_eq!(
+            format_condition(&cond, &LegendConfig::default())
+                .unwrap()
+                .unwrap(),
+            "class in park, garden"
+        );
+        let inverted = json!(["match", ["get", "class"], "water", false, true]);
+        assert_eq!(
+            format_condition(&inverted, &LegendConfig::default())
+                .unwrap()
+                .unwrap(),
+            "class != water"
+        );
+    }
+
+    #[test]
+    fn test_format_condition_geometry_type_and_legacy() {
+        let cond = json!(["==", ["geometry-type"], "Polygon"]);
+        assert_eq!(
+            format_condition(&cond, &LegendConfig::default())
+                .unwrap()
+                .unwrap(),
+            "geometry type == Polygon"
+        );
+        let legacy = json!(["all", ["==", "$type", "LineString"], ["!has", "bridge"]]);
+        assert_eq!(
+            format_condition(&legacy, &LegendConfig::default())
+                .unwrap()
+                .unwrap(),
+            "geometry type == LineString and without bridge"
+        );
+        assert_eq!(
+            format_condition(&json!(["==", "class", "park"]), &LegendConfig::default())
+                .unwrap()
+                .unwrap(),
+            "class == park"
+        );
     }
 
     #[test]
     fn test_format_condition_has() {
         let cond = json!(["has", "nombre"]);
-        assert_eq!(format_condition(&cond).unwrap(), "has nombre");
+        assert_eq!(
+            format_condition(&cond, &LegendConfig::default())
+                .unwrap()
+                .unwrap(),
+            "has nombre"
+        );
     }
 
     #[test]
     fn test_format_condition_not_has() {
         let cond = json!(["!", ["has", "nombre"]]);
-        assert_eq!(format_condition(&cond).unwrap(), "without nombre");
+        assert_eq!(
+            format_condition(&cond, &LegendConfig::default())
+                .unwrap()
+                .unwrap(),
+            "without nombre"
+        );
+    }
+
+    #[test]
+    fn test_format_condition_localized() {
+        let config = |locale: &str| LegendConfig {
+            locale: Some(locale.to_string()),
+            ..Default::default()
+        };
+        let cond = json!(["all", ["has", "name"], ["!", ["has", "ref"]]]);
+        assert_eq!(
+            format_condition(&cond, &config("es")).unwrap().unwrap(),
+            "tiene name y sin ref"
+        );
+        assert_eq!(
+            format_condition(&cond, &config("pt-BR")).unwrap().unwrap(),
+            "tem name e sem ref"
+        );
+        assert_eq!(
+            format_condition(&cond, &config("fr")).unwrap().unwrap(),
+            "has name and without ref"
+        );
+        let cond = json!(["none", ["in", "class", "a", "b"], ["==", "$type", "Point"]]);
+        assert_eq!(
+            format_condition(&cond, &config("es")).unwrap().unwrap(),
+            "no (class en a, b o tipo de geometría == Point)"
+        );
+    }
+
+    #[test]
+    fn test_localized_metadata_labels() {
+        let layer: Layer = serde_json::from_value(json!({
+            "id": "landuse", "type": "fill",
+            "paint": {"fill-color": ["match", ["get", "class"], "park", "#00ff00", "#cccccc"]},
+            "metadata": {"legend": {
+                "label": "Land use", "label:es": "Uso del suelo",
+                "custom-labels": ["Park", "Other"],
+                "custom-labels:pt": ["Parque", "Outro"],
+                "default:es": "Otro"
+            }}
+        }))
+        .unwrap();
+        let config = |locale: Option<&str>| LegendConfig {
+            locale: locale.map(|l| l.to_string()),
+            ..Default::default()
+        };
+        let paint = &layer.paint.as_ref().unwrap()["fill-color"];
+        let labels = |locale| {
+            let config = config(locale);
+            let entries = parse_expression(&layer, &config, paint).unwrap();
+            let title = get_layer_label(&layer, &config).unwrap();
+            (
+                title,
+                entries.into_iter().map(|(l, _)| l).collect::<Vec<_>>(),
+            )
+        };
+        assert_eq!(
+            labels(None),
+            ("Land use".into(), vec!["Park".into(), "Other".into()])
+        );
+        assert_eq!(
+            labels(Some("es")),
+            ("Uso del suelo".into(), vec!["Park".into(), "Other".into()])
+        );
+        assert_eq!(
+            labels(Some("pt-BR")),
+            ("Land use".into(), vec!["Parque".into(), "Outro".into()])
+        );
+        assert_eq!(
+            get_layer_default_label(&layer, &config(Some("es"))).unwrap(),
+            "Otro"
+        );
     }
 
     #[test]
     fn test_parse_expression_plain_string() {
         let layer: Layer = serde_json::from_value(json!({"id": "lyr", "type": "fill"})).unwrap();
-        let result = parse_expression(&layer, &json!("#abcdef")).unwrap();
+        let result = parse_expression(&layer, &LegendConfig::default(), &json!("#abcdef")).unwrap();
         assert_eq!(result.len(), 1);
         assert_eq!(result[0].1, "#abcdef");
     }
@@ -1157,11 +2401,197 @@ mod tests {
     #[test]
     fn test_parse_expression_plain_number() {
         let layer: Layer = serde_json::from_value(json!({"id": "lyr", "type": "fill"})).unwrap();
-        let result = parse_expression(&layer, &json!(42)).unwrap();
+        let result = parse_expression(&layer, &LegendConfig::default(), &json!(42)).unwrap();
         assert_eq!(result.len(), 1);
         assert_eq!(result[0].1, "42");
     }
 
+    #[test]
+    fn test_eval_constant_color_constructors() {
+        assert_eq!(
+            output_value(&json!(["rgb", 255, 0, 0])),
+            Some("#ff0000".to_string())
+        );
+        assert_eq!(
+            output_value(&json!(["rgba", 0, 0, 255, 0.5])),
+            Some("#0000ff80".to_string())
+        );
+        assert_eq!(
+            output_value(&json!(["to-color", ["get", "c"], "#f00"])),
+            Some("#ff0000".to_string())
+        );
+        assert_eq!(
+            output_value(&json!(["rgb", ["*", 2, 50], 0, ["/", 510, 2]])),
+            Some("#6400ff".to_string())
+        );
+        assert_eq!(output_value(&json!(["to-color", ["get", "c"]])), None);
+        // Fractional channels round the same way in `rgb` and `to-color` arrays.
+        assert_eq!(
+            output_value(&json!(["rgb", 127.6, 0.4, 254.5])),
+            output_value(&json!(["to-color", ["literal", [127.6, 0.4, 254.5]]]))
+        );
+        assert_eq!(
+            output_value(&json!(["to-color", ["literal", [127.6, 0.4, 254.5]]])),
+            Some("#8000ff".to_string())
+        );
+    }
+
+    #[test]
+    fn test_eval_constant_let_var() {
+        let expr = json!(["let", "base", ["rgb", 0, 255, 0], ["var", "base"]]);
+        assert_eq!(output_value(&expr), Some("#00ff00".to_string()));
+    }
+
+    #[test]
+    fn test_malformed_let_is_an_error() {
+        let layer: Layer = serde_json::from_value(json!({"id": "test", "type": "fill"})).unwrap();
+        let config = LegendConfig::default();
+        for expr in [
+            json!(["let", "x", 1]),
+            json!(["let"]),
+            json!([
+                "match",
+                ["get", "class"],
+                "park",
+                ["let", "c", "#f00"],
+                "#ccc"
+            ]),
+        ] {
+            let err = parse_expression(&layer, &config, &expr).unwrap_err();
+            assert!(err.to_string().contains("Malformed 'let' expression"));
+        }
+        assert_eq!(eval_constant(&json!(["let", "x", 1])), None);
+        assert_eq!(eval_constant(&json!(["let"])), None);
+    }
+
+    #[test]
+    fn test_parse_match_color_constructors() {
+        let layer: Layer = serde_json::from_value(json!({"id": "test", "type": "fill"})).unwrap();
+        let expr = json!([
+            "match",
+            ["get", "tipo"],
+            "a",
+            ["rgb", 255, 0, 0],
+            "b",
+            ["to-color", "#00f"],
+            ["rgba", 0, 0, 0, 0.5]
+        ]);
+        let result = parse_expression(&layer, &LegendConfig::default(), &expr).unwrap();
+        assert_eq!(result[0].1, "#ff0000");
+        assert_eq!(result[1].1, "#0000ff");
+        assert_eq!(result[2].1, "#00000080");
+    }
+
+    #[test]
+    fn test_parse_let_wrapping_step() {
+        let layer: Layer = serde_json::from_value(json!({"id": "test", "type": "fill"})).unwrap();
+        let expr = json!([
+            "let",
+            "low",
+            ["rgb", 255, 255, 0],
+            "high",
+            "#ff0000",
+            ["step", ["get", "n"], ["var", "low"], 10, ["var", "high"]]
+        ]);
+        let result = parse_expression(&layer, &LegendConfig::default(), &expr).unwrap();
+        assert_eq!(result[0], ("n < 10".to_string(), "#ffff00".to_string()));
+        assert_eq!(result[1], ("n ≥ 10".to_string(), "#ff0000".to_string()));
+    }
+
+    #[test]
+    fn test_parse_expression_top_level_rgb() {
+        let layer: Layer = serde_json::from_value(json!({"id": "lyr", "type": "fill"})).unwrap();
+        let result =
+            parse_expression(&layer, &LegendConfig::default(), &json!(["rgb", 0, 128, 0])).unwrap();
+        assert_eq!(result, vec![("lyr".to_string(), "#008000".to_string())]);
+        assert_eq!(
+            extract_color(Some(&json!(["rgb", 0, 128, 0]))).unwrap(),
+            "#008000"
+        );
+    }
+
+    #[test]
+    fn test_parse_legacy_categorical_function() {
+        let layer: Layer = serde_json::from_value(json!({
+            "id": "landuse", "type": "fill",
+            "metadata": {"legend": {"default": "Other"}}
+        }))
+        .unwrap();
+        let func = json!({
+            "property": "class",
+            "type": "categorical",
+            "stops": [["park", "#00ff00"], ["water", "#0000ff"]],
+            "default": "#cccccc"
+        });
+        let result = parse_expression(&layer, &LegendConfig::default(), &func).unwrap();
+        assert_eq!(
+            result,
+            vec![
+                ("park".to_string(), "#00ff00".to_string()),
+                ("water".to_string(), "#0000ff".to_string()),
+                ("Other".to_string(), "#cccccc".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_legacy_categorical_without_default() {
+        let layer: Layer = serde_json::from_value(json!({"id": "t", "type": "fill"})).unwrap();
+        let func = json!({
+            "property": "class", "type": "categorical",
+            "stops": [["a", "#ff0000"], ["b", "#00ff00"]]
+        });
+        let result = parse_expression(&layer, &LegendConfig::default(), &func).unwrap();
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[1], ("b".to_string(), "#00ff00".to_string()));
+    }
+
+    #[test]
+    fn test_parse_legacy_interval_function() {
+        let layer: Layer = serde_json::from_value(json!({"id": "t", "type": "fill"})).unwrap();
+        let func = json!({
+            "property": "pop", "type": "interval",
+            "stops": [[0, "#ffffcc"], [100, "#fd8d3c"], [1000, "#800026"]]
+        });
+        let result = parse_expression(&layer, &LegendConfig::default(), &func).unwrap();
+        assert_eq!(result.len(), 3);
+        assert_eq!(result[0], ("pop < 100".to_string(), "#ffffcc".to_string()));
+        assert_eq!(result[2], ("pop ≥ 1000".to_string(), "#800026".to_string()));
+    }
+
+    #[test]
+    fn test_parse_legacy_exponential_zoom_function() {
+        let layer: Layer = serde_json::from_value(json!({"id": "t", "type": "line"})).unwrap();
+        let func = json!({"base": 1.4, "stops": [[10, "#ffffff"], [18, "#ff0000"]]});
+        let result = parse_expression(&layer, &LegendConfig::default(), &func).unwrap();
+        assert_eq!(result[0], ("zoom ≥ 10".to_string(), "#ffffff".to_string()));
+        assert_eq!(result[1], ("zoom ≥ 18".to_string(), "#ff0000".to_string()));
+    }
+
+    #[test]
+    fn test_parse_legacy_identity_function() {
+        let layer: Layer = serde_json::from_value(json!({"id": "t", "type": "fill"})).unwrap();
+        let func = json!({"property": "color", "type": "identity", "default": "#123456"});
+        let result = parse_expression(&layer, &LegendConfig::default(), &func).unwrap();
+        assert_eq!(result, vec![("t".to_string(), "#123456".to_string())]);
+    }
+
+    #[test]
+    fn test_parse_legacy_composite_function_uses_values() {
+        let layer: Layer = serde_json::from_value(json!({"id": "t", "type": "fill"})).unwrap();
+        let func = json!({
+            "property": "rank", "type": "interval",
+            "stops": [
+                [{"zoom": 0, "value": 0}, "#000000"],
+                [{"zoom": 0, "value": 5}, "#ffffff"],
+                [{"zoom": 10, "value": 5}, "#ffffff"]
+            ]
+        });
+        let result = parse_expression(&layer, &LegendConfig::default(), &func).unwrap();
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[1].0, "rank ≥ 5");
+    }
+
     #[test]
     fn test_deserialize_sprite_string() {
         let json = r#"{"layers": [], "sprite": "https://example.com/sprites"}"#;
@@ -1192,4 +2622,142 @@ mod tests {
         let style: Style = serde_json::from_str(json).unwrap();
         assert!(style.sprite.is_empty());
     }
+
+    #[test]
+    fn test_deserialize_style_fields_and_sources() {
+        let style: Style = serde_json::from_value(json!({
+            "version": 8,
+            "name": "Basic",
+            "metadata": {"maputnik:renderer": "mlgljs"},
+            "glyphs": "https://example.com/fonts/{fontstack}/{range}.pbf",
+            "sources": {
+                "tiles": {"type": "vector", "url": "https://example.com/tiles.json",
+                          "attribution": "© Example"},
+                "imagery": {"type": "raster", "tiles": ["https://example.com/{z}/{x}/{y}.png"]},
+                "stations": {"type": "geojson", "cluster": true, "clusterRadius": 40,
+                             "clusterMaxZoom": 14,
+                             "data": {"type": "FeatureCollection", "features": []}}
+            },
+            "layers": [{
+                "id": "stations", "type": "circle", "source": "stations",
+                "minzoom": 5, "maxzoom": 16.5
+            }, {
+                "id": "roads", "type": "line", "source": "tiles",
+                "source-layer": "transportation", "filter": ["==", "class", "primary"]
+            }]
+        }))
+        .unwrap();
+        assert_eq!(style.version, Some(8));
+        assert_eq!(style.name.as_deref(), Some("Basic"));
+        assert!(style.metadata.is_some());
+        assert!(style.glyphs.as_deref().unwrap().contains("{fontstack}"));
+        assert_eq!(style.sources["tiles"].source_type, "vector");
+        assert_eq!(
+            style.sources["tiles"].url.as_deref(),
+            Some("https://example.com/tiles.json")
+        );
+        assert_eq!(style.sources["imagery"].tiles.len(), 1);
+
+        let stations = style.layer("stations").unwrap();
+        assert_eq!(
+            (stations.minzoom, stations.maxzoom),
+            (Some(5.0), Some(16.5))
+        );
+        let source = style.layer_source(stations).unwrap();
+        assert!(source.cluster);
+        assert_eq!(source.cluster_radius, Some(40.0));
+        assert_eq!(source.cluster_max_zoom, Some(14.0));
+        assert!(source.data.as_ref().unwrap().is_object());
+
+        let roads = style.layer("roads").unwrap();
+        assert_eq!(roads.source_layer.as_deref(), Some("transportation"));
+        assert!(roads.filter.is_some());
+        assert_eq!(
+            style.layer_source(roads).unwrap().attribution.as_deref(),
+            Some("© Example")
+        );
+        assert!(style.layer("missing").is_none());
+    }
+
+    #[test]
+    fn test_get_layer_label_from_filter() {
+        let layer: Layer = serde_json::from_value(json!({
+            "id": "parks", "type": "fill",
+            "filter": ["==", ["get", "class"], "park"]
+        }))
+        .unwrap();
+        let config = LegendConfig {
+            label_from_filter: true,
+            ..Default::default()
+        };
+        assert_eq!(get_layer_label(&layer, &config).unwrap(), "class == park");
+        // Opt-in only.
+        assert_eq!(
+            get_layer_label(&layer, &LegendConfig::default()).unwrap(),
+            "parks"
+        );
+    }
+
+    #[test]
+    fn test_get_layer_label_unsupported_filter_uses_id() {
+        let layer: Layer = serde_json::from_value(json!({
+            "id": "parks", "type": "fill",
+            "filter": ["all", ["==", ["get", "class"], "park"], ["within", {}]]
+        }))
+        .unwrap();
+        let config = LegendConfig {
+            label_from_filter: true,
+            ..Default::default()
+        };
+        assert_eq!(get_layer_label(&layer, &config).unwrap(), "parks");
+    }
+
+    #[test]
+    fn test_get_layer_label_prefers_metadata_over_filter() {
+        let layer: Layer = serde_json::from_value(json!({
+            "id": "parks", "type": "fill",
+            "filter": ["==", "class", "park"],
+            "metadata": {"legend": {"label": "Parks"}}
+        }))
+        .unwrap();
+        let config = LegendConfig {
+            label_from_filter: true,
+            ..Default::default()
+        };
+        assert_eq!(get_layer_label(&layer, &config).unwrap(), "Parks");
+    }
+
+    #[test]
+    fn test_get_layer_group() {
+        let layer: Layer = serde_json::from_value(json!({
+            "id": "parks", "type": "fill", "source-layer": "landuse",
+            "metadata": {"legend": {"group": "Land use"}}
+        }))
+        .unwrap();
+        let by_source_layer = LegendConfig {
+            group_by_source_layer: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            get_layer_group(&layer, &by_source_layer)
+                .unwrap()
+                .as_deref(),
+            Some("Land use")
+        );
+
+        let layer: Layer = serde_json::from_value(json!({
+            "id": "parks", "type": "fill", "source-layer": "landuse"
+        }))
+        .unwrap();
+        assert_eq!(
+            get_layer_group(&layer, &by_source_layer)
+                .unwrap()
+                .as_deref(),
+            Some("landuse")
+        );
+        assert_eq!(
+            get_layer_group(&layer, &LegendConfig::default()).unwrap(),
+            None
+        );
+    }
 }
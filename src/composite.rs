@@ -0,0 +1,362 @@
+use crate::{
+    LegendConfig,
+    common::{Layer, constant_color, get_fill_and_opacity, get_legend_object, render_label},
+    error::LegendError,
+    layout::inline_width,
+};
+use serde_json::Value;
+use svg::Document;
+use svg::node::element::{Circle, Line, Rectangle};
+
+/// Returns the key under which [`crate::MapLibreLegend::render_all`] merges a layer into a
+/// composite swatch, or `None` if the layer is drawn on its own.
+///
+/// Layers declare their symbol with `metadata.legend.composite`. When
+/// [`LegendConfig::auto_composite`] is enabled, layers sharing `source-layer`, `filter` and
+/// zoom range (`minzoom` / `maxzoom`) are merged as well. Only single-color `fill`, `line` and `circle` layers take part.
+pub fn composite_key(layer: &Layer, config: &LegendConfig) -> Result<Option<String>, LegendError> {
+    if !is_composable(layer) {
+        return Ok(None);
+    }
+    let legend = get_legend_object(layer)?;
+    if let Some(name) = legend
+        .and_then(|l| l.get("composite"))
+        .and_then(|c| c.as_str())
+    {
+        return Ok(Some(format!("composite:{}", name)));
+    }
+    if config.auto_composite
+        && let Some(source_layer) = &layer.source_layer
+    {
+        let filter = layer.filter.as_ref().unwrap_or(&Value::Null);
+        return Ok(Some(format!(
+            "source-layer:{}:{}:{:?}:{:?}",
+            source_layer, filter, layer.minzoom, layer.maxzoom
+        )));
+    }
+    Ok(None)
+}
+
+/// Whether a layer can be drawn as part of a composite swatch: a `fill`, `line` or `circle`
+/// layer whose color is a constant and that uses no pattern or gradient.
+fn is_composable(layer: &Layer) -> bool {
+    let Some(paint) = layer.paint.as_ref().and_then(|p| p.as_object()) else {
+        return false;
+    };
+    let color_key = match layer.layer_type.as_str() {
+        "fill" if !paint.contains_key("fill-pattern") => "fill-color",
+        "line" if !paint.contains_key("line-pattern") && !paint.contains_key("line-gradient") => {
+            "line-color"
+        }
+        "circle" => "circle-color",
+        _ => return false,
+    };
+    constant_color(paint.get(color_key)).is_some()
+}
+
+/// Renders layers that form one visual symbol (a road casing under a road line, a fill
+/// with an outline line, ...) as a single swatch with one label.
+///
+/// Members are drawn on top of each other in the given order, so they should be passed
+/// in style order. When the symbol contains a `fill`, `line` members outline the
+/// rectangle; otherwise they are drawn as a sample line. Line widths are scaled down
+/// together so the widest fits the swatch. The label is the first explicit
+/// `metadata.legend.label` among the members, or the top-most member's label.
+///
+/// Returns `(document, width, height)`.
+pub fn render_composite(
+    layers: &[&Layer],
+    config: &LegendConfig,
+) -> Result<(Document, u32, u32), LegendError> {
+    let &LegendConfig {
+        default_height,
+        has_label,
+        ref theme,
+        ..
+    } = config;
+    let (padding, swatch_width, swatch_height) =
+        (theme.padding, theme.swatch_width, theme.swatch_height);
+    let has_fill = layers.iter().any(|l| l.layer_type == "fill");
+    let max_line_width = layers
+        .iter()
+        .filter(|l| l.layer_type == "line")
+        .filter_map(|l| l.paint.as_ref().and_then(|p| p.as_object()))
+        .map(line_width)
+        .fold(0.0, f64::max);
+    let scale = if max_line_width > swatch_height as f64 {
+        swatch_height as f64 / max_line_width
+    } else {
+        1.0
+    };
+    let center_y = padding + swatch_height / 2;
+
+    let label_layer = label_layer(layers)?;
+    let width = match label_layer {
+        Some(layer) => inline_width(layer, config, theme.inline_label_x())?,
+        None => config.default_width,
+    };
+    let mut doc = Document::new()
+        .set("width", width)
+        .set("height", default_height);
+
+    for layer in layers {
+        let Some(paint) = layer.paint.as_ref().and_then(|p| p.as_object()) else {
+            continue;
+        };
+        match layer.layer_type.as_str() {
+            "fill" => {
+                let (fill, opacity) = paint_color(paint, "fill-color", "fill-opacity");
+                let mut rect = Rectangle::new()
+                    .set("x", padding)
+                    .set("y", padding)
+                    .set("width", swatch_width)
+                    .set("height", swatch_height)
+                    .set("fill", fill)
+                    .set("fill-opacity", opacity);
+                match constant_color(paint.get("fill-outline-color")) {
+                    Some(outline) => {
+                        let (stroke, stroke_opacity) = get_fill_and_opacity(&outline, 1.0);
+                        rect = rect
+                            .set("stroke", stroke)
+                            .set("stroke-opacity", stroke_opacity)
+                            .set("stroke-width", 1);
+                    }
+                    None => rect = rect.set("stroke", "none"),
+                }
+                doc = doc.add(rect);
+            }
+            "line" => {
+                let (stroke, opacity) = paint_color(paint, "line-color", "line-opacity");
+                let width = line_width(paint) * scale;
+                if has_fill {
+                    doc = doc.add(
+                        Rectangle::new()
+                            .set("x", padding)
+                            .set("y", padding)
+                            .set("width", swatch_width)
+                            .set("height", swatch_height)
+                            .set("fill", "none")
+                            .set("stroke", stroke)
+                            .set("stroke-opacity", opacity)
+                            .set("stroke-width", width),
+                    );
+                } else {
+                    let linecap = layer
+                        .layout
+                        .as_ref()
+                        .and_then(|l| l.get("line-cap"))
+                        .and_then(|v| v.as_str())
+                        .unwrap_or("butt");
+                    doc = doc.add(
+                        Line::new()
+                            .set("x1", padding)
+                            .set("y1", center_y)
+                            .set("x2", padding + swatch_width)
+                            .set("y2", center_y)
+                            .set("stroke", stroke)
+                            .set("stroke-width", width)
+                            .set("stroke-opacity", opacity)
+                            .set("stroke-linecap", linecap),
+                    );
+                }
+            }
+            "circle" => {
+                let (fill, opacity) = paint_color(paint, "circle-color", "circle-opacity");
+                let radius = paint
+                    .get("circle-radius")
+                    .and_then(|v| v.as_f64())
+                    .unwrap_or(10.0)
+                    .min((swatch_height / 2) as f64);
+                let (stroke, stroke_opacity) = get_fill_and_opacity(
+                    &constant_color(paint.get("circle-stroke-color"))
+                        .unwrap_or("black".to_string()),
+                    paint
+                        .get("circle-stroke-opacity")
+                        .and_then(|v| v.as_f64())
+                        .unwrap_or(1.0),
+                );
+                doc = doc.add(
+                    Circle::new()
+                        .set("cx", padding + swatch_width / 2)
+                        .set("cy", center_y)
+                        .set("r", radius)
+                        .set("fill", fill)
+                        .set("fill-opacity", opacity)
+                        .set("stroke", stroke)
+                        .set("stroke-opacity", stroke_opacity)
+                        .set(
+                            "stroke-width",
+                            paint
+                                .get("circle-stroke-width")
+                                .and_then(|v| v.as_f64())
+                                .unwrap_or(0.0),
+                        ),
+                );
+            }
+            _ => {}
+        }
+    }
+
+    if has_label && let Some(label_layer) = label_layer {
+        render_label(label_layer, config, &mut doc, None, None, None)?;
+    }
+
+    Ok((doc, width, default_height))
+}
+
+/// Picks the member whose label names the symbol: the first one with an explicit
+/// `metadata.legend.label`, otherwise the top-most member.
+pub fn label_layer<'a>(layers: &[&'a Layer]) -> Result<Option<&'a Layer>, LegendError> {
+    for layer in layers {
+        if get_legend_object(layer)?.is_some_and(|l| l.contains_key("label")) {
+            return Ok(Some(layer));
+        }
+    }
+    Ok(layers.last().copied())
+}
+
+fn paint_color(
+    paint: &serde_json::Map<String, Value>,
+    color_key: &str,
+    opacity_key: &str,
+) -> (String, f64) {
+    let color = constant_color(paint.get(color_key)).unwrap_or("black".to_string());
+    let opacity = paint
+        .get(opacity_key)
+        .and_then(|v| v.as_f64())
+        .unwrap_or(1.0);
+    get_fill_and_opacity(&color, opacity)
+}
+
+/// Returns a representative `line-width`: the constant value, or the last output of a
+/// zoom `interpolate`/`step` (the width at high zoom). Defaults to `3`, as in `render_line`.
+fn line_width(paint: &serde_json::Map<String, Value>) -> f64 {
+    match paint.get("line-width") {
+        Some(Value::Number(n)) => n.as_f64(),
+        Some(Value::Array(arr))
+            if matches!(
+                arr.first().and_then(|v| v.as_str()),
+                Some("interpolate" | "step")
+            ) =>
+        {
+            arr.last().and_then(|v| v.as_f64())
+        }
+        _ => None,
+    }
+    .unwrap_or(3.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn layer(v: Value) -> Layer {
+        serde_json::from_value(v).unwrap()
+    }
+
+    #[test]
+    fn test_composite_key_declared_and_auto() {
+        let declared = layer(json!({
+            "id": "road-casing", "type": "line",
+            "paint": {"line-color": "#333333"},
+            "metadata": {"legend": {"composite": "road"}}
+        }));
+        assert_eq!(
+            composite_key(&declared, &LegendConfig::default()).unwrap(),
+            Some("composite:road".to_string())
+        );
+
+        let auto = layer(json!({
+            "id": "road", "type": "line", "source-layer": "transportation",
+            "filter": ["==", "class", "primary"],
+            "paint": {"line-color": "#ffcc00"}
+        }));
+        assert_eq!(
+            composite_key(&auto, &LegendConfig::default()).unwrap(),
+            None
+        );
+        let config = LegendConfig {
+            auto_composite: true,
+            ..Default::default()
+        };
+        assert!(composite_key(&auto, &config).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_composite_key_separates_zoom_ranges() {
+        let config = LegendConfig {
+            auto_composite: true,
+            ..Default::default()
+        };
+        let landuse = |id: &str, minzoom: f64, maxzoom: f64| {
+            layer(json!({
+                "id": id, "type": "fill", "source-layer": "landuse",
+                "minzoom": minzoom, "maxzoom": maxzoom,
+                "paint": {"fill-color": "#00ff00"}
+            }))
+        };
+        let low = composite_key(&landuse("landuse-low", 0.0, 10.0), &config).unwrap();
+        let high = composite_key(&landuse("landuse-high", 10.0, 24.0), &config).unwrap();
+        assert!(low.is_some());
+        assert_ne!(low, high);
+    }
+
+    #[test]
+    fn test_composite_key_skips_data_driven_layers() {
+        let l = layer(json!({
+            "id": "road", "type": "line", "source-layer": "transportation",
+            "paint": {"line-color": ["match", ["get", "class"], "primary", "#f00", "#000"]},
+            "metadata": {"legend": {"composite": "road"}}
+        }));
+        let config = LegendConfig {
+            auto_composite: true,
+            ..Default::default()
+        };
+        assert_eq!(composite_key(&l, &config).unwrap(), None);
+    }
+
+    #[test]
+    fn test_render_composite_road_casing() {
+        let casing = layer(json!({
+            "id": "road-casing", "type": "line",
+            "paint": {"line-color": "#555555", "line-width": ["interpolate", ["linear"], ["zoom"], 10, 4, 18, 40]}
+        }));
+        let road = layer(json!({
+            "id": "road", "type": "line",
+            "paint": {"line-color": "#ffcc00", "line-width": 20}
+        }));
+        let (svg, _, height) =
+            render_composite(&[&casing, &road], &LegendConfig::default()).unwrap();
+        let svg = svg.to_string();
+        assert_eq!(height, 40);
+        // Casing first (underneath), widths scaled so the widest fits the swatch.
+        let casing_pos = svg.find("#555555").unwrap();
+        let road_pos = svg.find("#ffcc00").unwrap();
+        assert!(casing_pos < road_pos);
+        assert!(svg.contains("stroke-width=\"20\""));
+        assert!(svg.contains("stroke-width=\"10\""));
+        // One label, from the top-most member.
+        assert!(svg.contains("road"));
+        assert!(!svg.contains("road-casing"));
+    }
+
+    #[test]
+    fn test_render_composite_fill_with_outline() {
+        let fill = layer(json!({
+            "id": "water", "type": "fill",
+            "paint": {"fill-color": "#a0c8f0"},
+            "metadata": {"legend": {"label": "Water"}}
+        }));
+        let outline = layer(json!({
+            "id": "water-outline", "type": "line",
+            "paint": {"line-color": "#4a80b0", "line-width": 2}
+        }));
+        let (svg, _, _) = render_composite(&[&fill, &outline], &LegendConfig::default()).unwrap();
+        let svg = svg.to_string();
+        assert_eq!(svg.matches("<rect").count(), 2);
+        assert!(svg.contains("fill=\"none\""));
+        assert!(svg.contains("Water"));
+        assert!(!svg.contains("water-outline"));
+    }
+}
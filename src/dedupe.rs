@@ -0,0 +1,200 @@
+use crate::{
+    Color, LegendConfig,
+    common::{Layer, get_layer_label},
+    entries::layer_entries,
+    error::LegendError,
+};
+use image::DynamicImage;
+use serde_json::Value;
+use std::collections::HashSet;
+
+/// Legend rows already drawn by [`crate::MapLibreLegend::render_all`], keyed by
+/// `(layer type, color, label)`, with colors in their normalized [`Color`] form.
+pub type SeenEntries = HashSet<(String, String, String)>;
+
+/// What is left of a layer once the rows already drawn are dropped.
+#[derive(Debug, PartialEq)]
+pub enum Unseen {
+    /// Every row was already drawn.
+    Nothing,
+    /// The layer is drawn as usual.
+    Layer,
+    /// Only these rows are drawn.
+    Rows(Vec<(String, String)>),
+}
+
+/// Returns what is left of the layer without the legend rows already in `seen`, recording
+/// the new ones.
+///
+/// Layers drawn as a single swatch (`background`, `heatmap`, text-only `symbol`, ...)
+/// count as one row keyed by their whole paint and layout.
+pub fn retain_unseen(
+    layer: &Layer,
+    config: &LegendConfig,
+    sprites: &[(DynamicImage, Value)],
+    seen: &mut SeenEntries,
+) -> Result<Unseen, LegendError> {
+    let Some(entries) = layer_entries(layer, config, sprites)? else {
+        let style = format!(
+            "{}{}",
+            layer.paint.as_ref().unwrap_or(&Value::Null),
+            layer.layout.as_ref().unwrap_or(&Value::Null)
+        );
+        let entry = (
+            layer.layer_type.clone(),
+            style,
+            get_layer_label(layer, config)?,
+        );
+        return Ok(if seen.insert(entry) {
+            Unseen::Layer
+        } else {
+            Unseen::Nothing
+        });
+    };
+
+    let kept: Vec<(String, String)> = entries
+        .iter()
+        .filter(|(label, color)| {
+            // `white`, `#fff` and `rgb(255, 255, 255)` are the same row; icon names are
+            // kept as they are.
+            let color = Color::parse(color).map_or_else(|| color.clone(), |c| c.to_string());
+            seen.insert((layer.layer_type.clone(), color, label.clone()))
+        })
+        .cloned()
+        .collect();
+    Ok(if kept.is_empty() {
+        Unseen::Nothing
+    } else if kept.len() == entries.len() {
+        Unseen::Layer
+    } else {
+        Unseen::Rows(kept)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn landuse(id: &str, classes: Value) -> Layer {
+        serde_json::from_value(json!({
+            "id": id, "type": "fill",
+            "paint": {"fill-color": classes}
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_retain_unseen_drops_repeated_layer() {
+        let expr = json!(["match", ["get", "class"], "park", "#00ff00", "#cccccc"]);
+        let mut seen = SeenEntries::new();
+        let config = LegendConfig::default();
+        let first = landuse("landuse-z10", expr.clone());
+        assert_eq!(
+            retain_unseen(&first, &config, &[], &mut seen).unwrap(),
+            Unseen::Layer
+        );
+        let second = landuse("landuse-z14", expr);
+        // The default row is labelled with the layer id, so it differs between the two.
+        assert_eq!(
+            retain_unseen(&second, &config, &[], &mut seen).unwrap(),
+            Unseen::Rows(vec![("landuse-z14".to_string(), "#cccccc".to_string())])
+        );
+    }
+
+    #[test]
+    fn test_retain_unseen_keeps_only_new_rows() {
+        let mut seen = SeenEntries::new();
+        let config = LegendConfig::default();
+        let first = landuse(
+            "a",
+            json!([
+                "match",
+                ["get", "class"],
+                "park",
+                "#00ff00",
+                "wood",
+                "#008000",
+                "#ccc"
+            ]),
+        );
+        retain_unseen(&first, &config, &[], &mut seen).unwrap();
+        let second = landuse(
+            "b",
+            json!([
+                "match",
+                ["get", "class"],
+                "park",
+                "#00ff00",
+                "sand",
+                "#ffff00",
+                "#ccc"
+            ]),
+        );
+        assert_eq!(
+            retain_unseen(&second, &config, &[], &mut seen).unwrap(),
+            Unseen::Rows(vec![
+                ("sand".to_string(), "#ffff00".to_string()),
+                ("b".to_string(), "#ccc".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_retain_unseen_normalizes_colors() {
+        let mut seen = SeenEntries::new();
+        let config = LegendConfig::default();
+        let first = landuse(
+            "a",
+            json!([
+                "match",
+                ["get", "class"],
+                "park",
+                "#00ff00",
+                "sand",
+                "#ff0",
+                "#fff"
+            ]),
+        );
+        retain_unseen(&first, &config, &[], &mut seen).unwrap();
+        let second = landuse(
+            "b",
+            json!([
+                "match",
+                ["get", "class"],
+                "park",
+                "lime",
+                "sand",
+                "rgb(255, 255, 0)",
+                "water",
+                "#0000FF",
+                "white"
+            ]),
+        );
+        assert_eq!(
+            retain_unseen(&second, &config, &[], &mut seen).unwrap(),
+            Unseen::Rows(vec![
+                ("water".to_string(), "#0000FF".to_string()),
+                ("b".to_string(), "white".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_retain_unseen_single_swatch_layers() {
+        let mut seen = SeenEntries::new();
+        let config = LegendConfig::default();
+        let background: Layer = serde_json::from_value(json!({
+            "id": "bg", "type": "background", "paint": {"background-color": "#fff"}
+        }))
+        .unwrap();
+        assert_eq!(
+            retain_unseen(&background, &config, &[], &mut seen).unwrap(),
+            Unseen::Layer
+        );
+        assert_eq!(
+            retain_unseen(&background, &config, &[], &mut seen).unwrap(),
+            Unseen::Nothing
+        );
+    }
+}
@@ -1,6 +1,8 @@
 use crate::{
+    LegendConfig,
     common::{Layer, render_label},
     error::LegendError,
+    layout::inline_width,
 };
 use svg::Document;
 use svg::node::element::Rectangle;
@@ -9,34 +11,39 @@ use svg::node::element::Rectangle;
 ///
 /// Shows a gray rectangle (`#cccccc`) with 80% opacity, optionally with the layer label.
 ///
-/// Returns `(svg_string, width, height)`.
+/// Returns `(document, width, height)`.
 pub fn render_default(
     layer: &Layer,
-    default_width: u32,
-    default_height: u32,
-    has_label: bool,
-) -> Result<(String, u32, u32), LegendError> {
+    config: &LegendConfig,
+) -> Result<(Document, u32, u32), LegendError> {
+    let &LegendConfig {
+        default_height,
+        has_label,
+        ref theme,
+        ..
+    } = config;
+    let width = inline_width(layer, config, theme.inline_label_x())?;
     let color = "#cccccc";
     let outline_color = "#333333";
     let opacity = 0.8;
 
     let mut doc = Document::new()
-        .set("width", default_width)
+        .set("width", width)
         .set("height", default_height);
 
     let rect = Rectangle::new()
-        .set("x", 10)
-        .set("y", 10)
-        .set("width", 30)
-        .set("height", 20)
+        .set("x", theme.padding)
+        .set("y", theme.padding)
+        .set("width", theme.swatch_width)
+        .set("height", theme.swatch_height)
         .set("fill", color)
         .set("fill-opacity", opacity)
         .set("stroke", outline_color);
     doc = doc.add(rect);
 
     if has_label {
-        render_label(layer, &mut doc, None, None, None)?;
+        render_label(layer, config, &mut doc, None, None, None)?;
     }
 
-    Ok((doc.to_string(), default_width, default_height))
+    Ok((doc, width, default_height))
 }
@@ -0,0 +1,100 @@
+use crate::{
+    LegendConfig,
+    common::{Layer, get_legend_object, parse_expression, pattern_cases},
+    error::LegendError,
+    line::is_progress_gradient,
+};
+use image::DynamicImage;
+use serde_json::Value;
+
+/// Returns the `(label, value)` rows a layer is drawn with, or `None` for layers drawn as
+/// a single swatch (`background`, `heatmap`, `raster`, text-only `symbol`, `line-gradient`,
+/// `fill-extrusion` with a `height-legend`, ...).
+///
+/// As in the renderers, pattern rows are used only when some pattern is in `sprites`.
+pub fn layer_entries(
+    layer: &Layer,
+    config: &LegendConfig,
+    sprites: &[(DynamicImage, Value)],
+) -> Result<Option<Vec<(String, String)>>, LegendError> {
+    let has_height_legend = get_legend_object(layer)?
+        .and_then(|l| l.get("height-legend"))
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    if has_height_legend {
+        return Ok(None);
+    }
+    let pattern = match layer.layer_type.as_str() {
+        "fill" => paint_value(layer, "fill-pattern"),
+        "line" => paint_value(layer, "line-pattern"),
+        _ => None,
+    };
+    if let Some(expr) = pattern
+        && let Some(cases) = pattern_cases(layer, config, expr, sprites)?
+    {
+        return Ok(Some(cases));
+    }
+    match entry_expression(layer) {
+        Some(expr) => Ok(Some(parse_expression(layer, config, expr)?)),
+        None => Ok(None),
+    }
+}
+
+/// Returns the color (or `icon-image`) expression producing a layer's legend rows when
+/// no pattern is drawn.
+fn entry_expression(layer: &Layer) -> Option<&Value> {
+    match layer.layer_type.as_str() {
+        "fill" => paint_value(layer, "fill-color"),
+        "line" if paint_value(layer, "line-gradient").is_some_and(is_progress_gradient) => None,
+        "line" => paint_value(layer, "line-color"),
+        "circle" => paint_value(layer, "circle-color"),
+        "fill-extrusion" => paint_value(layer, "fill-extrusion-color"),
+        // A single icon is drawn as one swatch with an inline label.
+        "symbol" => layer
+            .layout
+            .as_ref()?
+            .get("icon-image")
+            .filter(|expr| expr.is_array()),
+        _ => None,
+    }
+}
+
+fn paint_value<'a>(layer: &'a Layer, key: &str) -> Option<&'a Value> {
+    layer.paint.as_ref()?.get(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_layer_entries_pattern_without_sprites_uses_color() {
+        let layer: Layer = serde_json::from_value(json!({
+            "id": "landuse", "type": "fill",
+            "paint": {
+                "fill-pattern": ["match", ["get", "class"], "park", "grass", "dots"],
+                "fill-color": ["match", ["get", "class"], "park", "#0f0", "sand", "#ff0", "#ccc"]
+            }
+        }))
+        .unwrap();
+        let entries = layer_entries(&layer, &LegendConfig::default(), &[])
+            .unwrap()
+            .unwrap();
+        let colors: Vec<&str> = entries.iter().map(|(_, color)| color.as_str()).collect();
+        assert_eq!(colors, ["#0f0", "#ff0", "#ccc"]);
+    }
+
+    #[test]
+    fn test_layer_entries_single_swatch_layers() {
+        let config = LegendConfig::default();
+        for layer in [
+            json!({"id": "bg", "type": "background", "paint": {"background-color": "#fff"}}),
+            json!({"id": "poi", "type": "symbol", "layout": {"icon-image": "star"}}),
+            json!({"id": "route", "type": "line", "paint": {"line-gradient": ["interpolate", ["linear"], ["line-progress"], 0, "#f00", 1, "#00f"]}}),
+        ] {
+            let layer: Layer = serde_json::from_value(layer).unwrap();
+            assert!(layer_entries(&layer, &config, &[]).unwrap().is_none());
+        }
+    }
+}
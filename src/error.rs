@@ -4,7 +4,10 @@ use serde_json::Error as SerdeJsonError;
 use thiserror::Error;
 
 /// Errors that can occur while parsing a MapLibre style or rendering a legend.
+///
+/// New variants may be added in minor releases, so `match`es need a wildcard arm.
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum LegendError {
     /// HTTP request to fetch the sprite PNG failed.
     #[error("Failed to fetch sprite PNG: {0}")]
@@ -30,6 +33,9 @@ pub enum LegendError {
     /// A MapLibre paint expression is malformed or uses an unsupported operator.
     #[error("Invalid expression: {0}")]
     InvalidExpression(String),
+    /// A string could not be parsed as a CSS color.
+    #[error("Invalid color: {0}")]
+    InvalidColor(String),
     /// Top-level style JSON could not be deserialized into a [`Style`](crate::common::Style).
     #[error("JSON deserialization failed: {0}")]
     Deserialization(SerdeJsonError),
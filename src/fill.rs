@@ -1,102 +1,147 @@
 use crate::{
+    LegendConfig,
     common::{
-        FONT_SIZE, ICON_HEIGHT, Layer, PADDING, ROW_HEIGHT, extract_color, get_fill_and_opacity,
-        parse_expression, render_label, render_separator,
+        FALLBACK_COLOR, Layer, constant_color, extract_color, get_fill_and_opacity,
+        has_sprite_icon, parse_expression, pattern_cases, render_label, sprite_pattern, svg_id,
     },
     error::LegendError,
+    layout::{RowLayout, inline_width},
 };
+use image::DynamicImage;
+use serde_json::Value;
 use svg::Document;
-use svg::node::element::{Rectangle, Text as SvgText};
+use svg::node::element::{Definitions, Rectangle};
 
 /// Renders a `fill` layer legend as an SVG.
 ///
 /// - Single-color paint: one rectangle with the layer label alongside.
 /// - Expression-based paint (`match`, `case`, `interpolate`, `step`, `coalesce`):
 ///   one rectangle per case, stacked vertically with labels.
+/// - `fill-pattern` (takes precedence over `fill-color`, as in MapLibre): the sprite image
+///   is tiled into each rectangle; data-driven patterns produce one swatch per case. Cases
+///   without a pattern (`""` or an icon missing from the sprite) use the constant
+///   `fill-color`, and the layer is drawn from `fill-color` when no pattern can be drawn.
 ///
-/// Returns `(svg_string, width, height)`.
+/// `entries`, when given, are drawn in place of the rows parsed from the paint (e.g. the
+/// rows of a layer split between columns).
+///
+/// Returns `(document, width, height)`.
 pub fn render_fill(
     layer: &Layer,
-    paint: &serde_json::Map<String, serde_json::Value>,
-    default_width: u32,
-    default_height: u32,
-    has_label: bool,
-) -> Result<(String, u32, u32), LegendError> {
-    let color_expr = paint.get("fill-color").ok_or_else(|| {
-        LegendError::InvalidJson(format!(
-            "Layer '{}': missing 'fill-color' in paint",
-            layer.id
-        ))
-    })?;
-    let cases = parse_expression(layer, color_expr)?;
+    paint: &serde_json::Map<String, Value>,
+    config: &LegendConfig,
+    sprite_data: &[(DynamicImage, Value)],
+    entries: Option<&[(String, String)]>,
+) -> Result<(Document, u32, u32), LegendError> {
+    let &LegendConfig {
+        default_height,
+        has_label,
+        ref theme,
+        ..
+    } = config;
+    let pattern_cases = match paint.get("fill-pattern") {
+        Some(expr) => pattern_cases(layer, config, expr, sprite_data)?,
+        None => None,
+    };
+    // Rows whose pattern is missing from the sprite are drawn with the fill color.
+    let fallback_color =
+        constant_color(paint.get("fill-color")).unwrap_or_else(|| FALLBACK_COLOR.to_string());
+    let fallback_expr = Value::String(FALLBACK_COLOR.to_string());
+    let color_expr = match paint.get("fill-color") {
+        Some(color) => color,
+        None if paint.contains_key("fill-pattern") => &fallback_expr,
+        None => {
+            return Err(LegendError::InvalidJson(format!(
+                "Layer '{}': missing 'fill-color' in paint",
+                layer.id
+            )));
+        }
+    };
+    let cases = match (entries, &pattern_cases) {
+        (Some(entries), _) => entries.to_vec(),
+        (None, Some(cases)) => cases.clone(),
+        (None, None) => parse_expression(layer, config, color_expr)?,
+    };
     let opacity = paint
         .get("fill-opacity")
         .and_then(|v| v.as_f64())
         .unwrap_or(1.0);
-    let fill_outline_color =
-        extract_color(paint.get("fill-outline-color")).unwrap_or("black".to_string());
-    let mut init_y = PADDING as i32;
+    let (fill_outline_color, outline_opacity) = get_fill_and_opacity(
+        &extract_color(paint.get("fill-outline-color")).unwrap_or("black".to_string()),
+        1.0,
+    );
+    let rows = RowLayout::new(layer, config, &cases, theme.label_x())?;
+    let mut init_y = theme.padding as i32;
     let dynamic_height = if cases.is_empty() {
         0
     } else {
-        ICON_HEIGHT + cases.len() as u32 * ROW_HEIGHT
+        theme.swatch_height + rows.rows_height
     };
     let height = if !cases.is_empty() {
-        if has_label {
-            init_y += ROW_HEIGHT as i32;
-            dynamic_height + ICON_HEIGHT
+        if rows.title_row {
+            init_y += theme.title_height() as i32;
+            dynamic_height + theme.title_space()
         } else {
             dynamic_height
         }
     } else {
         default_height
     };
-    let mut doc = Document::new()
-        .set("width", default_width)
-        .set("height", height);
+    let width = if cases.is_empty() {
+        inline_width(layer, config, theme.inline_label_x())?
+    } else {
+        rows.width
+    };
+    let mut doc = Document::new().set("width", width).set("height", height);
     if !cases.is_empty() {
         if has_label {
-            render_label(layer, &mut doc, Some(10), Some(20), Some(true))?;
-            render_separator(&mut doc, default_width, 0, 10);
+            rows.render_title(layer, config, &mut doc, theme.baseline(init_y))?;
         }
-        for (i, (label, color)) in cases.iter().enumerate() {
-            let y = init_y + i as i32 * ROW_HEIGHT as i32;
-            let (fill_value, effective_opacity) = get_fill_and_opacity(color, opacity);
+        for (i, (_, color)) in cases.iter().enumerate() {
+            let (dx, y) = rows.position(i, init_y);
+            let (fill_value, effective_opacity) = if pattern_cases.is_none() {
+                get_fill_and_opacity(color, opacity)
+            } else if has_sprite_icon(sprite_data, color) {
+                let id = svg_id(layer, &format!("pattern-{}", i));
+                let x = theme.padding + dx;
+                let pattern = sprite_pattern(sprite_data, color, &id, x, y as u32)?;
+                doc = doc.add(Definitions::new().add(pattern));
+                (format!("url(#{})", id), opacity)
+            } else {
+                get_fill_and_opacity(&fallback_color, opacity)
+            };
             let rect = Rectangle::new()
-                .set("x", PADDING)
+                .set("x", theme.padding + dx)
                 .set("y", y)
-                .set("width", 30)
-                .set("height", ICON_HEIGHT)
+                .set("width", theme.swatch_width)
+                .set("height", theme.swatch_height)
                 .set("fill", fill_value.as_str())
                 .set("fill-opacity", effective_opacity)
                 .set("stroke", fill_outline_color.as_str())
+                .set("stroke-opacity", outline_opacity)
                 .set("stroke-width", "1");
-            let text = SvgText::new("")
-                .set("x", 45)
-                .set("y", y + 15)
-                .set("font-size", FONT_SIZE)
-                .set("fill", "black")
-                .add(svg::node::Text::new(label.clone()));
-            doc = doc.add(rect).add(text);
+            let baseline = theme.baseline(y);
+            rows.render_entry(theme, &mut doc, i, rect, theme.label_x() + dx, baseline);
         }
     } else {
         let color = extract_color(Some(color_expr))?;
         let (fill_value, effective_opacity) = get_fill_and_opacity(&color, opacity);
         let rect = Rectangle::new()
-            .set("x", PADDING)
-            .set("y", PADDING)
-            .set("width", 30)
-            .set("height", ICON_HEIGHT)
+            .set("x", theme.padding)
+            .set("y", theme.padding)
+            .set("width", theme.swatch_width)
+            .set("height", theme.swatch_height)
             .set("fill", fill_value)
             .set("fill-opacity", effective_opacity)
             .set("stroke", fill_outline_color)
+            .set("stroke-opacity", outline_opacity)
             .set("stroke-width", "1");
         doc = doc.add(rect);
         if has_label {
-            render_label(layer, &mut doc, None, None, None)?;
+            render_label(layer, config, &mut doc, None, None, None)?;
         }
     }
-    Ok((doc.to_string(), default_width, height))
+    Ok((doc, width, height))
 }
 
 #[cfg(test)]
@@ -105,6 +150,23 @@ mod tests {
     use crate::common::Layer;
     use serde_json::json;
 
+    fn config(has_label: bool) -> LegendConfig {
+        LegendConfig {
+            has_label,
+            ..Default::default()
+        }
+    }
+
+    fn sprites() -> Vec<(DynamicImage, Value)> {
+        vec![(
+            DynamicImage::new_rgba8(8, 4),
+            json!({
+                "hatch": {"x": 0, "y": 0, "width": 4, "height": 4, "pixelRatio": 1},
+                "dots": {"x": 4, "y": 0, "width": 4, "height": 4, "pixelRatio": 2}
+            }),
+        )]
+    }
+
     fn make_layer(id: &str) -> Layer {
         serde_json::from_value(json!({"id": id, "type": "fill"})).unwrap()
     }
@@ -126,8 +188,9 @@ mod tests {
         let layer = make_layer("test");
         let p = paint(json!({"fill-color": "#ff0000"}));
         // parse_expression returns 1 entry for a plain string → multi-case path:
-        // height = ICON_HEIGHT(20) + 1 * ROW_HEIGHT(30) = 50
-        let (svg, width, height) = render_fill(&layer, &p, 200, 40, false).unwrap();
+        // height = swatch_height(20) + 1 * row_height(30) = 50
+        let (svg, width, height) = render_fill(&layer, &p, &config(false), &[], None).unwrap();
+        let svg = svg.to_string();
         assert_eq!(width, 200);
         assert_eq!(height, 50);
         assert!(svg.contains("#ff0000"));
@@ -139,10 +202,11 @@ mod tests {
         let p = paint(json!({
             "fill-color": ["match", ["get", "tipo"], "bosque", "#228B22", "#cccccc"]
         }));
-        let (svg, _, height) = render_fill(&layer, &p, 200, 40, false).unwrap();
-        assert!(svg.contains("#228B22"));
+        let (svg, _, height) = render_fill(&layer, &p, &config(false), &[], None).unwrap();
+        let svg = svg.to_string();
+        assert!(svg.contains("#228b22"));
         assert!(svg.contains("bosque"));
-        // 2 cases × ROW_HEIGHT(30) + ICON_HEIGHT(20) = 80
+        // 2 cases × row_height(30) + swatch_height(20) = 80
         assert_eq!(height, 80);
     }
 
@@ -152,18 +216,79 @@ mod tests {
         let p = paint(json!({
             "fill-color": ["match", ["get", "tipo"], "a", "#ff0000", "#cccccc"]
         }));
-        let (svg, _, height_with) = render_fill(&layer, &p, 200, 40, true).unwrap();
+        let (svg, _, height_with) = render_fill(&layer, &p, &config(true), &[], None).unwrap();
+        let svg = svg.to_string();
         assert!(svg.contains("Mi Capa"));
-        // With label: +ROW_HEIGHT offset + ICON_HEIGHT extra
-        let (_, _, height_without) = render_fill(&make_layer("lyr"), &p, 200, 40, false).unwrap();
+        // With label: +title_height offset + title_space extra
+        let (_, _, height_without) =
+            render_fill(&make_layer("lyr"), &p, &config(false), &[], None).unwrap();
         assert!(height_with > height_without);
     }
 
+    #[test]
+    fn test_render_fill_horizontal_entries_on_title_line() {
+        let layer = make_layer_with_label("lyr", "Mi Capa");
+        let p = paint(json!({
+            "fill-color": ["match", ["get", "tipo"], "a", "#ff0000", "b", "#00ff00", "#cccccc"]
+        }));
+        let config = LegendConfig {
+            orientation: crate::Orientation::Horizontal,
+            default_width: 400,
+            ..Default::default()
+        };
+        let (svg, width, height) = render_fill(&layer, &p, &config, &[], None).unwrap();
+        let svg = svg.to_string();
+        assert!(svg.contains("Mi Capa"));
+        // Title and entries share one row, sized to the content.
+        assert_eq!(height, 50);
+        assert!(width < 400);
+        assert!(!svg.contains("<line"));
+    }
+
+    #[test]
+    fn test_render_fill_wraps_long_labels() {
+        let layer = make_layer("test");
+        let p = paint(json!({
+            "fill-color": ["match", ["get", "v"], "a", "#ff0000", "#cccccc"],
+        }));
+        let config = LegendConfig {
+            has_label: false,
+            label_width: Some(60),
+            ..Default::default()
+        };
+        let layer = Layer {
+            metadata: Some(json!({"legend": {
+                "custom-labels": ["From $100 to $250 per square meter"],
+                "default": "Other"
+            }})),
+            ..layer
+        };
+        let (svg, _, height) = render_fill(&layer, &p, &config, &[], None).unwrap();
+        let svg = svg.to_string();
+        let lines = svg.matches("<tspan").count();
+        assert!(lines > 1);
+        // Each extra line adds a line height (17) to the first row.
+        assert_eq!(height, 80 + (lines as u32 - 1) * 17);
+        assert!(svg.contains("Other"));
+
+        let config = LegendConfig {
+            label_overflow: crate::LabelOverflow::Truncate,
+            ..config
+        };
+        let (svg, _, height) = render_fill(&layer, &p, &config, &[], None).unwrap();
+        let svg = svg.to_string();
+        assert_eq!(height, 80);
+        assert!(!svg.contains("<tspan"));
+        assert!(svg.contains("<title>"));
+        assert!(svg.contains("…"));
+    }
+
     #[test]
     fn test_render_fill_with_opacity() {
         let layer = make_layer("test");
         let p = paint(json!({"fill-color": "#ff0000", "fill-opacity": 0.5}));
-        let (svg, _, _) = render_fill(&layer, &p, 200, 40, false).unwrap();
+        let (svg, _, _) = render_fill(&layer, &p, &config(false), &[], None).unwrap();
+        let svg = svg.to_string();
         assert!(svg.contains("fill-opacity"));
         assert!(svg.contains("0.5"));
     }
@@ -172,6 +297,63 @@ mod tests {
     fn test_render_fill_missing_color_returns_err() {
         let layer = make_layer("test");
         let p = paint(json!({}));
-        assert!(render_fill(&layer, &p, 200, 40, false).is_err());
+        assert!(render_fill(&layer, &p, &config(false), &[], None).is_err());
+    }
+
+    #[test]
+    fn test_render_fill_pattern_only() {
+        let layer = make_layer("test");
+        let p = paint(json!({"fill-pattern": "hatch"}));
+        let (svg, _, height) = render_fill(&layer, &p, &config(false), &sprites(), None).unwrap();
+        let svg = svg.to_string();
+        assert!(svg.contains("<pattern"));
+        assert!(svg.contains("url(#test-pattern-0)"));
+        assert!(svg.contains("data:image/png;base64,"));
+        assert_eq!(height, 50);
+    }
+
+    #[test]
+    fn test_render_fill_pattern_match_one_swatch_per_case() {
+        let layer = make_layer("test");
+        let p = paint(json!({
+            "fill-color": "#ff0000",
+            "fill-pattern": ["match", ["get", "tipo"], "bosque", "hatch", "dots"]
+        }));
+        let (svg, _, _) = render_fill(&layer, &p, &config(false), &sprites(), None).unwrap();
+        let svg = svg.to_string();
+        assert!(svg.contains("url(#test-pattern-0)"));
+        assert!(svg.contains("url(#test-pattern-1)"));
+        assert!(svg.contains("bosque"));
+        // pixelRatio 2 halves the tile size of "dots"
+        assert!(svg.contains(r#"height="2""#));
+        assert!(!svg.contains("#ff0000"));
+    }
+
+    #[test]
+    fn test_render_fill_pattern_without_sprites_uses_fill_color() {
+        let layer = make_layer("test");
+        let p = paint(json!({"fill-pattern": "hatch", "fill-color": "#ff0000"}));
+        let (svg, _, _) = render_fill(&layer, &p, &config(false), &[], None).unwrap();
+        let svg = svg.to_string();
+        assert!(!svg.contains("<pattern"));
+        assert!(svg.contains("#ff0000"));
+
+        let p = paint(json!({"fill-pattern": "unknown"}));
+        let (svg, _, _) = render_fill(&layer, &p, &config(false), &sprites(), None).unwrap();
+        assert!(svg.to_string().contains(FALLBACK_COLOR));
+    }
+
+    #[test]
+    fn test_render_fill_pattern_match_empty_default() {
+        let layer = make_layer("test");
+        let p = paint(json!({
+            "fill-color": "#ff0000",
+            "fill-pattern": ["match", ["get", "tipo"], "bosque", "hatch", ""]
+        }));
+        let (svg, _, _) = render_fill(&layer, &p, &config(false), &sprites(), None).unwrap();
+        let svg = svg.to_string();
+        assert!(svg.contains("url(#test-pattern-0)"));
+        assert!(!svg.contains("test-pattern-1"));
+        assert!(svg.contains(r##"fill="#ff0000""##));
     }
 }
@@ -1,36 +1,65 @@
 use crate::{
+    LegendConfig,
     common::{
-        ICON_HEIGHT, Layer, PADDING, ROW_HEIGHT, extract_color, get_fill_and_opacity,
-        parse_expression, render_label, render_separator,
+        FALLBACK_COLOR, Layer, extract_color, format_condition, get_fill_and_opacity,
+        get_legend_object, legend_str, match_label, numeric_input_field, parse_expression,
+        render_label, render_separator, render_text,
     },
     error::LegendError,
+    layout::{RowLayout, inline_width, text_width},
+    locale::words,
+    numbers::NumberFormat,
 };
+use serde_json::Value;
 use svg::Document;
-use svg::node::element::{Polygon, Text as SvgText};
+use svg::node::element::{Group, Polygon};
+
+/// Outline color of the isometric block faces.
+const EDGE_COLOR: &str = "#333333";
+
+/// Width in pixels of the front face of an isometric block.
+const BLOCK_WIDTH: u32 = 22;
+
+/// Horizontal and vertical offset in pixels of the block's back edge (the visible depth).
+const BLOCK_DEPTH: u32 = 6;
+
+/// Height in pixels of the tallest block in the graduated height legend.
+const MAX_BLOCK_HEIGHT: f64 = 40.0;
 
 /// Renders a `fill-extrusion` layer.
 ///
-/// Single-color layers are shown as an isometric 3D box to visually distinguish them
-/// from plain `fill` layers. Expression-based layers render as a list of colored
-/// rectangles (same approach as `fill`).
+/// Every entry is drawn as an isometric 3D box to visually distinguish extrusions from
+/// plain `fill` layers: a single box for a literal color, or one box per case with its
+/// label, laid out by [`RowLayout`], for expression-based colors (or for `entries`, when
+/// given).
+///
+/// When `fill-extrusion-height` is data-driven and the layer sets
+/// `metadata.legend.height-legend` to `true`, a graduated section follows the color
+/// entries, with one box per height stop drawn proportionally to the stop value, under a
+/// heading (`metadata.legend.height-label`, or "Height").
 pub fn render_fill_extrusion(
     layer: &Layer,
-    paint: &serde_json::Map<String, serde_json::Value>,
-    default_width: u32,
-    default_height: u32,
-    has_label: bool,
-) -> Result<(String, u32, u32), LegendError> {
+    paint: &serde_json::Map<String, Value>,
+    config: &LegendConfig,
+    entries: Option<&[(String, String)]>,
+) -> Result<(Document, u32, u32), LegendError> {
+    let &LegendConfig {
+        default_height,
+        has_label,
+        ref theme,
+        ..
+    } = config;
     let color_expr = paint.get("fill-extrusion-color").ok_or_else(|| {
         LegendError::InvalidJson(format!(
             "Layer '{}': missing 'fill-extrusion-color' in paint",
             layer.id
         ))
     })?;
-    let is_expression = color_expr.is_array();
-    let cases = if is_expression {
-        parse_expression(layer, color_expr)?
-    } else {
-        vec![]
+    let is_expression = color_expr.is_array() || color_expr.is_object();
+    let cases = match entries {
+        Some(entries) => entries.to_vec(),
+        None if is_expression => parse_expression(layer, config, color_expr)?,
+        None => vec![],
     };
 
     let opacity = paint
@@ -38,100 +67,294 @@ pub fn render_fill_extrusion(
         .and_then(|v| v.as_f64())
         .unwrap_or(1.0);
 
-    let mut init_y: i32 = PADDING as i32;
+    let heights = height_stops(layer, config, paint)?;
+
+    let label_x = theme.label_x();
+    let rows = RowLayout::new(layer, config, &cases, label_x)?;
+    let mut init_y: i32 = theme.padding as i32;
     let dynamic_height = if cases.is_empty() {
         0
     } else {
-        ICON_HEIGHT + cases.len() as u32 * ROW_HEIGHT
+        theme.swatch_height + rows.rows_height
     };
-    let height = if !cases.is_empty() {
-        if has_label {
-            init_y += ROW_HEIGHT as i32;
-            dynamic_height + ICON_HEIGHT
+    let mut height = if !cases.is_empty() {
+        if rows.title_row {
+            init_y += theme.title_height() as i32;
+            dynamic_height + theme.title_space()
         } else {
             dynamic_height
         }
     } else {
         default_height
     };
+    let mut width = if cases.is_empty() {
+        inline_width(layer, config, label_x)?
+    } else {
+        rows.width
+    };
+    // The graduated height section is always a vertical list under its own heading.
+    let heading = match legend_str(layer, config, "height-label")? {
+        Some(heading) => heading,
+        None => words(config.locale.as_deref()).height,
+    };
+    if !heights.is_empty() {
+        let content = heights
+            .iter()
+            .map(|(label, _)| label_x + text_width(theme, label, false) + theme.padding)
+            .fold(width, u32::max)
+            .max(theme.padding + text_width(theme, heading, true) + theme.padding);
+        width = if config.auto_width {
+            config.max_width.map_or(content, |max| content.min(max))
+        } else {
+            config.default_width
+        };
+    }
 
-    let mut doc = Document::new()
-        .set("width", default_width)
-        .set("height", height);
+    let mut doc = Document::new().set("width", width);
 
     if !cases.is_empty() {
-        // Multi-case: rectangles with labels (same as fill)
         if has_label {
-            render_label(layer, &mut doc, Some(10), Some(20), Some(true))?;
-            render_separator(&mut doc, default_width, 0, 10);
+            rows.render_title(layer, config, &mut doc, theme.baseline(init_y))?;
         }
-        for (i, (label, color)) in cases.iter().enumerate() {
-            let y = init_y + i as i32 * ROW_HEIGHT as i32;
+        for (i, (_, color)) in cases.iter().enumerate() {
+            let (dx, y) = rows.position(i, init_y);
             let (fill_value, effective_opacity) = get_fill_and_opacity(color, opacity);
-            let rect = svg::node::element::Rectangle::new()
-                .set("x", PADDING)
-                .set("y", y)
-                .set("width", 30)
-                .set("height", ICON_HEIGHT)
-                .set("fill", fill_value.as_str())
-                .set("fill-opacity", effective_opacity)
-                .set("stroke", "#333333")
-                .set("stroke-width", "1");
-            let text = SvgText::new("")
-                .set("x", 45)
-                .set("y", y + 15)
-                .set("font-size", 14)
-                .set("fill", "black")
-                .add(svg::node::Text::new(label.clone()));
-            doc = doc.add(rect).add(text);
+            let block = iso_block(
+                theme.padding + dx,
+                y as u32 + theme.swatch_height,
+                theme.swatch_height.saturating_sub(BLOCK_DEPTH),
+                &fill_value,
+                effective_opacity,
+            );
+            rows.render_entry(theme, &mut doc, i, block, label_x + dx, theme.baseline(y));
         }
     } else {
-        // Single color: isometric 3D box
-        // Geometry (depth offset = 8px right, 8px up):
-        //   Top face:   (10,17) (30,17) (38, 9) (18, 9)
-        //   Front face: (10,17) (30,17) (30,31) (10,31)
-        //   Side face:  (30,17) (38, 9) (38,23) (30,31)
         let color = extract_color(Some(color_expr))?;
         let (fill_value, effective_opacity) = get_fill_and_opacity(&color, opacity);
-        let stroke = "#333333";
-
-        let top = Polygon::new()
-            .set("points", "10,17 30,17 38,9 18,9")
-            .set("fill", fill_value.as_str())
-            .set("fill-opacity", effective_opacity * 0.65)
-            .set("stroke", stroke)
-            .set("stroke-width", 0.5);
-
-        let front = Polygon::new()
-            .set("points", "10,17 30,17 30,31 10,31")
-            .set("fill", fill_value.as_str())
-            .set("fill-opacity", effective_opacity)
-            .set("stroke", stroke)
-            .set("stroke-width", 0.5);
-
-        let side = Polygon::new()
-            .set("points", "30,17 38,9 38,23 30,31")
-            .set("fill", fill_value.as_str())
-            .set("fill-opacity", effective_opacity * 0.45)
-            .set("stroke", stroke)
-            .set("stroke-width", 0.5);
-
-        doc = doc.add(top).add(front).add(side);
+        doc = doc.add(iso_block(
+            theme.padding,
+            theme.padding + theme.swatch_height,
+            theme.swatch_height.saturating_sub(BLOCK_DEPTH),
+            &fill_value,
+            effective_opacity,
+        ));
 
         if has_label {
-            render_label(layer, &mut doc, Some(45), Some(25), None)?;
+            let baseline = theme.baseline(theme.padding as i32) as u32;
+            render_label(layer, config, &mut doc, Some(label_x), Some(baseline), None)?;
+        }
+    }
+
+    if !heights.is_empty() {
+        // Graduated blocks share one neutral (or the literal) color so only height varies.
+        let color = if is_expression {
+            FALLBACK_COLOR.to_string()
+        } else {
+            extract_color(Some(color_expr))?
+        };
+        let (fill_value, effective_opacity) = get_fill_and_opacity(&color, opacity);
+        let max = heights.iter().map(|(_, h)| *h).fold(f64::MIN, f64::max);
+        render_separator(&mut doc, theme, width, 0, height - theme.padding);
+        let heading_baseline = height + theme.title_baseline();
+        render_text(
+            &mut doc,
+            theme,
+            heading,
+            theme.padding,
+            heading_baseline,
+            true,
+        );
+        let mut y = heading_baseline + theme.padding;
+        for (label, value) in &heights {
+            let block_h = if max > 0.0 {
+                (value / max * MAX_BLOCK_HEIGHT).round().max(2.0) as u32
+            } else {
+                2
+            };
+            let bottom = y + BLOCK_DEPTH + block_h;
+            doc = doc.add(iso_block(
+                theme.padding,
+                bottom,
+                block_h,
+                &fill_value,
+                effective_opacity,
+            ));
+            let baseline = bottom - block_h / 2;
+            render_text(&mut doc, theme, label, label_x, baseline, false);
+            y = bottom + theme.padding;
         }
+        height = y;
     }
 
-    Ok((doc.to_string(), default_width, height))
+    doc = doc.set("height", height);
+    Ok((doc, width, height))
+}
+
+/// Reads the data-driven `fill-extrusion-height` stops when the layer opts into the
+/// graduated height legend via `metadata.legend.height-legend`.
+///
+/// Returns `(label, height)` pairs with labels of their own, so the `custom-labels` and
+/// `default` label meant for the color entries never apply:
+///
+/// - `interpolate` / `step` stops are labelled from the input like color stops
+///   (`levels ≥ 10`), following the layer's [`NumberFormat`];
+/// - `match` values by their labels and `case` branches by their conditions.
+///
+/// The fallback output of `match` and `case` is left out, and so are non-numeric outputs.
+/// Heights that do not depend on feature data, such as zoom-driven ones, have no stops.
+fn height_stops(
+    layer: &Layer,
+    config: &LegendConfig,
+    paint: &serde_json::Map<String, Value>,
+) -> Result<Vec<(String, f64)>, LegendError> {
+    let enabled = get_legend_object(layer)?
+        .and_then(|l| l.get("height-legend"))
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    let Some(arr) = paint
+        .get("fill-extrusion-height")
+        .and_then(|expr| expr.as_array())
+        .filter(|_| enabled)
+    else {
+        return Ok(vec![]);
+    };
+    let op = arr.first().and_then(|op| op.as_str());
+    let input = match op {
+        Some("interpolate") => arr.get(2),
+        Some("step" | "match") => arr.get(1),
+        Some("case") => arr
+            .get(1..arr.len().saturating_sub(1))
+            .and_then(|c| c.first()),
+        _ => None,
+    };
+    // Only stop-based expressions over feature data carry values worth grading;
+    // `["get", ...]` has none and a zoom curve describes no feature.
+    if !input.is_some_and(reads_feature_data) {
+        return Ok(vec![]);
+    }
+    let number = |v: &Value| v.as_f64();
+    let format = NumberFormat::for_layer(layer, config)?;
+    let mut stops = Vec::new();
+    match op {
+        Some("interpolate") => {
+            let field = arr.get(2).and_then(numeric_input_field).unwrap_or_default();
+            let pairs: Vec<&[Value]> = arr[3..].chunks_exact(2).collect();
+            let values: Vec<f64> = pairs.iter().filter_map(|p| number(&p[0])).collect();
+            if values.len() == pairs.len() {
+                for (i, pair) in pairs.iter().enumerate() {
+                    if let Some(h) = number(&pair[1]) {
+                        stops.push((format.stop(&field, &values, i), h));
+                    }
+                }
+            }
+        }
+        Some("step") => {
+            let field = arr.get(1).and_then(numeric_input_field).unwrap_or_default();
+            let thresholds: Vec<f64> = arr[3..].iter().step_by(2).filter_map(number).collect();
+            let outputs: Vec<&Value> = arr[2..].iter().step_by(2).collect();
+            for (i, output) in outputs.iter().enumerate() {
+                let label = match (i.checked_sub(1), thresholds.get(i)) {
+                    (None, Some(&next)) => format.below(&field, next),
+                    (Some(prev), Some(&next)) => format.range(&field, thresholds[prev], next),
+                    (Some(prev), None) => format.at_least(&field, thresholds[prev]),
+                    (None, None) => continue,
+                };
+                if let Some(h) = number(output) {
+                    stops.push((label, h));
+                }
+            }
+        }
+        Some("match") if arr.len() >= 5 => {
+            let field = numeric_input_field(&arr[1]).unwrap_or_default();
+            for pair in arr[2..arr.len() - 1].chunks_exact(2) {
+                if let (Some(label), Some(h)) = (match_label(&field, &pair[0]), number(&pair[1])) {
+                    stops.push((label, h));
+                }
+            }
+        }
+        Some("case") if arr.len() >= 4 => {
+            for pair in arr[1..arr.len() - 1].chunks_exact(2) {
+                if let (Some(label), Some(h)) =
+                    (format_condition(&pair[0], config)?, number(&pair[1]))
+                {
+                    stops.push((label, h));
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(stops)
+}
+
+/// Returns whether an expression reads feature data (properties, id, geometry type or
+/// feature state), as opposed to constants and camera inputs like `["zoom"]`.
+fn reads_feature_data(value: &Value) -> bool {
+    let Some(arr) = value.as_array() else {
+        return false;
+    };
+    match arr.first().and_then(|op| op.as_str()) {
+        Some("literal") => false,
+        Some("get" | "has" | "id" | "properties" | "geometry-type" | "feature-state") => true,
+        _ => arr.iter().any(reads_feature_data),
+    }
+}
+
+/// Builds an isometric box whose front face has its bottom-left corner at `(x, bottom)`.
+///
+/// The front face is [`BLOCK_WIDTH`] wide and `front_h` tall; the top and side faces
+/// extend [`BLOCK_DEPTH`] pixels up and to the right and are shaded lighter to suggest
+/// depth.
+fn iso_block(x: u32, bottom: u32, front_h: u32, fill: &str, opacity: f64) -> Group {
+    let (x0, x1, x2) = (x, x + BLOCK_WIDTH, x + BLOCK_WIDTH + BLOCK_DEPTH);
+    // Blocks at the top edge of a small-swatch legend are clipped rather than underflow.
+    let (y_top, y_bottom) = (bottom.saturating_sub(front_h), bottom);
+    let y_back = y_top.saturating_sub(BLOCK_DEPTH);
+
+    let face = |points: String, face_opacity: f64| {
+        Polygon::new()
+            .set("points", points)
+            .set("fill", fill)
+            .set("fill-opacity", face_opacity)
+            .set("stroke", EDGE_COLOR)
+            .set("stroke-width", 0.5)
+    };
+
+    Group::new()
+        .add(face(
+            format!(
+                "{x0},{y_top} {x1},{y_top} {x2},{y_back} {},{y_back}",
+                x0 + BLOCK_DEPTH
+            ),
+            opacity * 0.65,
+        ))
+        .add(face(
+            format!("{x0},{y_top} {x1},{y_top} {x1},{y_bottom} {x0},{y_bottom}"),
+            opacity,
+        ))
+        .add(face(
+            format!(
+                "{x1},{y_top} {x2},{y_back} {x2},{} {x1},{y_bottom}",
+                y_bottom.saturating_sub(BLOCK_DEPTH)
+            ),
+            opacity * 0.45,
+        ))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::LegendTheme;
     use crate::common::Layer;
     use serde_json::json;
 
+    fn config(has_label: bool) -> LegendConfig {
+        LegendConfig {
+            default_height: 50,
+            has_label,
+            ..Default::default()
+        }
+    }
+
     fn make_layer(id: &str) -> Layer {
         serde_json::from_value(json!({"id": id, "type": "fill-extrusion"})).unwrap()
     }
@@ -144,7 +367,8 @@ mod tests {
     fn test_render_fill_extrusion_single_color() {
         let layer = make_layer("test");
         let p = paint(json!({"fill-extrusion-color": "#ff0000"}));
-        let (svg, width, height) = render_fill_extrusion(&layer, &p, 200, 50, false).unwrap();
+        let (svg, width, height) = render_fill_extrusion(&layer, &p, &config(false), None).unwrap();
+        let svg = svg.to_string();
         assert_eq!(width, 200);
         assert_eq!(height, 50);
         // Single-color renders as an isometric 3D box (Polygon elements)
@@ -158,16 +382,40 @@ mod tests {
         let p = paint(json!({
             "fill-extrusion-color": ["match", ["get", "zona"], "A", "#ff0000", "#cccccc"]
         }));
-        let (svg, _, _) = render_fill_extrusion(&layer, &p, 200, 50, false).unwrap();
+        let (svg, _, _) = render_fill_extrusion(&layer, &p, &config(false), None).unwrap();
+        let svg = svg.to_string();
         assert!(svg.contains("#ff0000"));
         assert!(svg.contains('A'));
     }
 
+    #[test]
+    fn test_render_fill_extrusion_small_swatch() {
+        let layer = make_layer("test");
+        let config = LegendConfig {
+            has_label: false,
+            theme: LegendTheme {
+                swatch_height: 4,
+                padding: 0,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        for p in [
+            paint(json!({"fill-extrusion-color": "#ff0000"})),
+            paint(json!({
+                "fill-extrusion-color": ["match", ["get", "zona"], "A", "#ff0000", "#cccccc"]
+            })),
+        ] {
+            let (svg, _, _) = render_fill_extrusion(&layer, &p, &config, None).unwrap();
+            assert!(svg.to_string().contains("#ff0000"));
+        }
+    }
+
     #[test]
     fn test_render_fill_extrusion_missing_color_returns_err() {
         let layer = make_layer("test");
         let p = paint(json!({}));
-        assert!(render_fill_extrusion(&layer, &p, 200, 50, false).is_err());
+        assert!(render_fill_extrusion(&layer, &p, &config(false), None).is_err());
     }
 
     #[test]
@@ -176,8 +424,108 @@ mod tests {
         let p = paint(json!({
             "fill-extrusion-color": ["match", ["get", "z"], "a", "#ff0000", "b", "#00ff00", "#cccccc"]
         }));
-        let (_, _, height) = render_fill_extrusion(&layer, &p, 200, 50, false).unwrap();
-        // 3 cases × ROW_HEIGHT(30) + ICON_HEIGHT(20) = 110
+        let (_, _, height) = render_fill_extrusion(&layer, &p, &config(false), None).unwrap();
+        // 3 cases × row_height(30) + swatch_height(20) = 110
         assert_eq!(height, 110);
     }
+
+    #[test]
+    fn test_render_fill_extrusion_expression_uses_blocks() {
+        let layer = make_layer("test");
+        let p = paint(json!({
+            "fill-extrusion-color": ["match", ["get", "z"], "a", "#ff0000", "#cccccc"]
+        }));
+        let (svg, _, _) = render_fill_extrusion(&layer, &p, &config(false), None).unwrap();
+        let svg = svg.to_string();
+        // Two cases × three faces each, no flat rectangles
+        assert_eq!(svg.matches("<polygon").count(), 6);
+        assert!(!svg.contains("<rect"));
+    }
+
+    #[test]
+    fn test_render_fill_extrusion_height_legend() {
+        let layer: Layer = serde_json::from_value(json!({
+            "id": "buildings", "type": "fill-extrusion",
+            "metadata": {"legend": {"height-legend": true}}
+        }))
+        .unwrap();
+        let p = paint(json!({
+            "fill-extrusion-color": "#aaaaaa",
+            "fill-extrusion-height": ["step", ["get", "levels"], 10, 5, 20, 10, 40]
+        }));
+        let (svg, _, height) = render_fill_extrusion(&layer, &p, &config(false), None).unwrap();
+        let svg = svg.to_string();
+        // Color block + three graduated blocks
+        assert_eq!(svg.matches("<polygon").count(), 12);
+        assert!(svg.contains("levels ≥ 10"));
+        assert!(svg.contains("\nHeight\n</text>"));
+        assert!(height > 50);
+    }
+
+    #[test]
+    fn test_render_fill_extrusion_height_labels_ignore_color_labels() {
+        let layer: Layer = serde_json::from_value(json!({
+            "id": "buildings", "type": "fill-extrusion",
+            "metadata": {"legend": {
+                "height-legend": true, "height-label": "Floors",
+                "custom-labels": ["Homes", "Shops"], "default": "Other"
+            }}
+        }))
+        .unwrap();
+        let p = paint(json!({
+            "fill-extrusion-color": ["match", ["get", "use"], "home", "#ff0000", "shop", "#00ff00", "#cccccc"],
+            "fill-extrusion-height": ["match", ["get", "kind"], "tower", 100, ["house", "villa"], 10, 5]
+        }));
+        let heights = height_stops(&layer, &config(false), &p).unwrap();
+        assert_eq!(
+            heights,
+            [
+                ("tower".to_string(), 100.0),
+                ("house, villa".to_string(), 10.0)
+            ]
+        );
+        let (svg, _, _) = render_fill_extrusion(&layer, &p, &config(false), None).unwrap();
+        let svg = svg.to_string();
+        assert!(svg.contains("\nFloors\n</text>"));
+        assert_eq!(svg.matches("\nHomes\n</text>").count(), 1);
+    }
+
+    #[test]
+    fn test_height_stops_skip_zoom_curves() {
+        let layer: Layer = serde_json::from_value(json!({
+            "id": "buildings", "type": "fill-extrusion",
+            "metadata": {"legend": {"height-legend": true}}
+        }))
+        .unwrap();
+        let p = paint(json!({
+            "fill-extrusion-color": "#aaaaaa",
+            "fill-extrusion-height": ["interpolate", ["linear"], ["zoom"], 15, 0, 16, ["get", "height"]]
+        }));
+        assert!(height_stops(&layer, &config(false), &p).unwrap().is_empty());
+        let p = paint(json!({
+            "fill-extrusion-color": "#aaaaaa",
+            "fill-extrusion-height": ["interpolate", ["linear"], ["get", "levels"], 1, 3, 10, 30]
+        }));
+        let heights = height_stops(&layer, &config(false), &p).unwrap();
+        assert_eq!(
+            heights,
+            [
+                ("levels ≥ 1".to_string(), 3.0),
+                ("levels ≥ 10".to_string(), 30.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_fill_extrusion_height_legend_is_opt_in() {
+        let layer = make_layer("buildings");
+        let p = paint(json!({
+            "fill-extrusion-color": "#aaaaaa",
+            "fill-extrusion-height": ["step", ["get", "levels"], 10, 5, 20]
+        }));
+        let (svg, _, height) = render_fill_extrusion(&layer, &p, &config(false), None).unwrap();
+        let svg = svg.to_string();
+        assert_eq!(svg.matches("<polygon").count(), 3);
+        assert_eq!(height, 50);
+    }
 }
@@ -1,6 +1,8 @@
 use crate::{
-    common::{Layer, render_label},
+    LegendConfig,
+    common::{Layer, render_label, svg_id},
     error::LegendError,
+    layout::inline_width,
 };
 use svg::Document;
 use svg::node::element::{Definitions, RadialGradient, Rectangle, Stop};
@@ -10,18 +12,24 @@ use svg::node::element::{Definitions, RadialGradient, Rectangle, Stop};
 /// Shows a rectangle with a hard-coded radial gradient (dark red → light yellow) as a
 /// visual hint. The gradient does not reflect the actual `heatmap-color` expression.
 ///
-/// Returns `(svg_string, width, height)`.
+/// Returns `(document, width, height)`.
 pub fn render_heatmap(
     layer: &Layer,
-    default_width: u32,
-    default_height: u32,
-    has_label: bool,
-) -> Result<(String, u32, u32), LegendError> {
-    let total_w = 30.0;
-    let total_h = 20.0;
+    config: &LegendConfig,
+) -> Result<(Document, u32, u32), LegendError> {
+    let &LegendConfig {
+        default_height,
+        has_label,
+        ref theme,
+        ..
+    } = config;
+    let width = inline_width(layer, config, theme.inline_label_x())?;
+    let total_w = theme.swatch_width as f32;
+    let total_h = theme.swatch_height as f32;
 
+    let id = svg_id(layer, "gradient");
     let mut gradient = RadialGradient::new()
-        .set("id", "heatmap-gradient")
+        .set("id", id.as_str())
         .set("cx", "25%")
         .set("cy", "25%")
         .set("r", "75%");
@@ -37,12 +45,12 @@ pub fn render_heatmap(
     }
 
     let mut doc = Document::new()
-        .set("width", default_width)
+        .set("width", width)
         .set("height", default_height)
         .add(
             Rectangle::new()
-                .set("x", 10)
-                .set("y", 10)
+                .set("x", theme.padding)
+                .set("y", theme.padding)
                 .set("width", total_w)
                 .set("height", total_h)
                 .set("fill", "#f8f9fa"),
@@ -50,18 +58,18 @@ pub fn render_heatmap(
         .add(Definitions::new().add(gradient))
         .add(
             Rectangle::new()
-                .set("x", 10)
-                .set("y", 10)
+                .set("x", theme.padding)
+                .set("y", theme.padding)
                 .set("width", total_w)
                 .set("height", total_h)
-                .set("fill", "url(#heatmap-gradient)")
+                .set("fill", format!("url(#{})", id))
                 .set("stroke", "#495057")
                 .set("stroke-width", 0.5),
         );
 
     if has_label {
-        render_label(layer, &mut doc, None, None, None)?;
+        render_label(layer, config, &mut doc, None, None, None)?;
     }
 
-    Ok((doc.to_string(), default_width, default_height))
+    Ok((doc, width, default_height))
 }
@@ -0,0 +1,458 @@
+use crate::{
+    LegendConfig, LegendTheme,
+    common::{Layer, get_layer_label, render_label, render_separator, text_element},
+    error::LegendError,
+    metrics::measure,
+};
+use svg::node::element::{Group, TSpan, Title};
+use svg::{Document, Node};
+
+/// Direction in which legend entries are laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Orientation {
+    /// One entry per row, below the layer title and a separator.
+    #[default]
+    Vertical,
+    /// Entries left to right after an inline title, wrapping at `default_width`.
+    Horizontal,
+}
+
+/// How labels wider than [`LegendConfig::label_width`] are fitted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LabelOverflow {
+    /// Entry labels wrap onto several lines and their row grows to fit. Titles and the
+    /// labels of single-swatch legends, whose row cannot grow, are truncated.
+    #[default]
+    Wrap,
+    /// Labels are cut short with an ellipsis; the full text is kept as a tooltip.
+    Truncate,
+}
+
+/// Measures a label in the theme's label font, or its title font when `is_bold` is set,
+/// with the embedded font metrics (see [`crate::metrics`]).
+pub fn text_width(theme: &LegendTheme, text: &str, is_bold: bool) -> u32 {
+    if is_bold {
+        measure(text, theme.title_font_size, true)
+    } else {
+        measure(text, theme.font_size, false)
+    }
+}
+
+/// Distance between the baselines of the lines of a wrapped label.
+pub fn line_height(theme: &LegendTheme) -> u32 {
+    (theme.font_size as f64 * 1.2).round() as u32
+}
+
+/// Splits or shortens a label to fit [`LegendConfig::label_width`], returning the lines
+/// to draw. Labels only wrap when `can_wrap` is set and the overflow mode is
+/// [`LabelOverflow::Wrap`]; otherwise they are truncated with an ellipsis.
+pub fn fit_label(config: &LegendConfig, text: &str, is_bold: bool, can_wrap: bool) -> Vec<String> {
+    let theme = &config.theme;
+    let fits = |t: &str| {
+        config
+            .label_width
+            .is_none_or(|max| text_width(theme, t, is_bold) <= max)
+    };
+    if fits(text) {
+        return vec![text.to_string()];
+    }
+    if !(can_wrap && config.label_overflow == LabelOverflow::Wrap) {
+        let mut chars: Vec<char> = text.chars().collect();
+        while !chars.is_empty() {
+            chars.pop();
+            let truncated = format!("{}…", chars.iter().collect::<String>().trim_end());
+            if fits(&truncated) {
+                return vec![truncated];
+            }
+        }
+        return vec!["…".to_string()];
+    }
+
+    wrap_words(text, fits)
+}
+
+/// Wraps `text` onto lines at most `max_width` pixels wide, in the label or title font.
+pub fn wrap_text(theme: &LegendTheme, text: &str, is_bold: bool, max_width: u32) -> Vec<String> {
+    wrap_words(text, |t| text_width(theme, t, is_bold) <= max_width)
+}
+
+/// Greedily fills lines with the words of `text` while `fits` accepts them.
+fn wrap_words(text: &str, fits: impl Fn(&str) -> bool) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let candidate = if line.is_empty() {
+            word.to_string()
+        } else {
+            format!("{} {}", line, word)
+        };
+        if fits(&candidate) {
+            line = candidate;
+            continue;
+        }
+        if !line.is_empty() {
+            lines.push(std::mem::take(&mut line));
+        }
+        // Words wider than the label are broken between characters.
+        for c in word.chars() {
+            if !line.is_empty() && !fits(&format!("{}{}", line, c)) {
+                lines.push(std::mem::take(&mut line));
+            }
+            line.push(c);
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Draws the fitted `lines` of `text` with their first baseline at `(x, y)`: as a plain
+/// text element when the label fits, with a `<title>` tooltip holding the full text when
+/// it was truncated, or with one `<tspan>` per line when it wraps.
+pub fn render_lines(
+    doc: &mut impl Node,
+    theme: &LegendTheme,
+    text: &str,
+    lines: &[String],
+    x: u32,
+    y: i32,
+    is_bold: bool,
+) {
+    let element = text_element(theme, x, y, is_bold);
+    let element = match lines {
+        [line] if line == text => element.add(svg::node::Text::new(text)),
+        [line] => element
+            .add(Title::new(text))
+            .add(svg::node::Text::new(line.as_str())),
+        _ => lines
+            .iter()
+            .enumerate()
+            .fold(element, |element, (i, line)| {
+                element.add(
+                    TSpan::new(line.as_str())
+                        .set("x", x)
+                        .set("y", y + (i as u32 * line_height(theme)) as i32),
+                )
+            }),
+    };
+    doc.append(element);
+}
+
+/// Width of a legend whose widest content spans `content` pixels, margins included.
+///
+/// Vertical legends are `default_width` wide unless [`LegendConfig::auto_width`] sizes
+/// them to their content; horizontal ones always are. Content widths are capped at
+/// [`LegendConfig::max_width`].
+pub fn legend_width(config: &LegendConfig, content: u32) -> u32 {
+    if config.orientation == Orientation::Vertical && !config.auto_width {
+        return config.default_width;
+    }
+    config.max_width.map_or(content, |max| content.min(max))
+}
+
+/// Width of a layer title drawn on a row of its own, margins included.
+pub fn title_width(layer: &Layer, config: &LegendConfig) -> Result<u32, LegendError> {
+    let theme = &config.theme;
+    let title = fitted_width(config, &get_layer_label(layer, config)?, true, false);
+    Ok(theme.padding + title + theme.padding)
+}
+
+/// Width of the widest line of a label once fitted with [`fit_label`].
+fn fitted_width(config: &LegendConfig, text: &str, is_bold: bool, can_wrap: bool) -> u32 {
+    fit_label(config, text, is_bold, can_wrap)
+        .iter()
+        .map(|line| text_width(&config.theme, line, is_bold))
+        .max()
+        .unwrap_or(0)
+}
+
+/// Placement of a layer's legend rows, shared by the renderers that draw one row per entry.
+///
+/// Renderers keep their own swatches and vertical metrics: they ask for each entry's
+/// `(x offset, y)`, draw its label with [`RowLayout::render_entry`], and use
+/// [`RowLayout::rows_height`] in place of the entry count times the row height when sizing
+/// the legend. Rows of wrapped labels are taller by one line height per extra line.
+pub struct RowLayout {
+    /// Whether the title takes a row of its own above the entries (vertical layouts).
+    pub title_row: bool,
+    /// Total height of the entry rows.
+    pub rows_height: u32,
+    /// Width of the rendered legend.
+    pub width: u32,
+    /// `(x offset, y offset)` of each entry.
+    offsets: Vec<(u32, u32)>,
+    /// `(label, fitted lines)` of each entry.
+    labels: Vec<(String, Vec<String>)>,
+}
+
+impl RowLayout {
+    /// Lays out `entries`, whose labels the renderer draws at `label_x` with the swatch
+    /// starting at the theme padding.
+    pub fn new(
+        layer: &Layer,
+        config: &LegendConfig,
+        entries: &[(String, String)],
+        label_x: u32,
+    ) -> Result<Self, LegendError> {
+        let theme = &config.theme;
+        let padding = theme.padding;
+        let labels: Vec<(String, Vec<String>)> = entries
+            .iter()
+            .map(|(label, _)| (label.clone(), fit_label(config, label, false, true)))
+            .collect();
+        let label_width = |lines: &[String]| {
+            lines
+                .iter()
+                .map(|line| text_width(theme, line, false))
+                .max()
+                .unwrap_or(0)
+        };
+        let row_height = |lines: &[String]| {
+            theme.row_height + lines.len().saturating_sub(1) as u32 * line_height(theme)
+        };
+
+        if config.orientation == Orientation::Vertical {
+            let title = if config.has_label {
+                title_width(layer, config)?
+            } else {
+                0
+            };
+            let content = labels
+                .iter()
+                .map(|(_, lines)| label_x + label_width(lines) + padding)
+                .fold(title, u32::max);
+            let mut offsets = Vec::with_capacity(labels.len());
+            let mut y = 0;
+            for (_, lines) in &labels {
+                offsets.push((0, y));
+                y += row_height(lines);
+            }
+            return Ok(Self {
+                title_row: config.has_label,
+                rows_height: y,
+                width: legend_width(config, content),
+                offsets,
+                labels,
+            });
+        }
+
+        let mut dx = if config.has_label {
+            fitted_width(config, &get_layer_label(layer, config)?, true, false) + theme.entry_gap
+        } else {
+            0
+        };
+        let mut right = padding + dx;
+        // Entries are placed on lines of entries, each as tall as its tallest row.
+        let mut line_tops = vec![0];
+        let mut line_height = 0;
+        let mut offsets = Vec::with_capacity(labels.len());
+        for (_, lines) in &labels {
+            let entry_width = label_x - padding + label_width(lines);
+            if dx > 0 && padding + dx + entry_width + padding > config.default_width {
+                line_tops.push(line_tops.last().unwrap_or(&0) + line_height);
+                line_height = 0;
+                dx = 0;
+            }
+            offsets.push((dx, *line_tops.last().unwrap_or(&0)));
+            line_height = line_height.max(row_height(lines));
+            right = right.max(padding + dx + entry_width);
+            dx += entry_width + theme.entry_gap;
+        }
+        Ok(Self {
+            title_row: false,
+            rows_height: line_tops.last().unwrap_or(&0) + line_height,
+            width: legend_width(config, right + padding),
+            offsets,
+            labels,
+        })
+    }
+
+    /// Returns the x offset and the y of entry `i`, whose first row is at `init_y`.
+    pub fn position(&self, i: usize, init_y: i32) -> (u32, i32) {
+        let (dx, dy) = self.offsets.get(i).copied().unwrap_or_default();
+        (dx, init_y + dy as i32)
+    }
+
+    /// Draws entry `i`: its `swatch`, and its label with the first baseline at `(x, y)`,
+    /// wrapped or truncated as laid out. Both go in a group labelled for screen readers.
+    pub fn render_entry(
+        &self,
+        theme: &LegendTheme,
+        doc: &mut Document,
+        i: usize,
+        swatch: impl Into<Box<dyn Node>>,
+        x: u32,
+        y: i32,
+    ) {
+        let Some((label, lines)) = self.labels.get(i) else {
+            return;
+        };
+        let mut group = Group::new().set("aria-label", label.as_str()).add(swatch);
+        render_lines(&mut group, theme, label, lines, x, y, false);
+        doc.append(group);
+    }
+
+    /// Draws the layer title: bold above a separator vertically, or inline at the start
+    /// of the first row, on the entries' label `baseline`, horizontally.
+    pub fn render_title(
+        &self,
+        layer: &Layer,
+        config: &LegendConfig,
+        doc: &mut Document,
+        baseline: i32,
+    ) -> Result<(), LegendError> {
+        let theme = &config.theme;
+        if self.title_row {
+            render_label(
+                layer,
+                config,
+                doc,
+                Some(theme.padding),
+                Some(theme.title_baseline()),
+                Some(true),
+            )?;
+            render_separator(doc, theme, self.width, 0, theme.padding);
+        } else {
+            render_label(
+                layer,
+                config,
+                doc,
+                Some(theme.padding),
+                Some(baseline.max(0) as u32),
+                Some(true),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Width of a legend drawn as a single swatch with an inline label at `label_x`, as
+/// sized by [`legend_width`].
+pub fn inline_width(
+    layer: &Layer,
+    config: &LegendConfig,
+    label_x: u32,
+) -> Result<u32, LegendError> {
+    let label = if config.has_label {
+        fitted_width(config, &get_layer_label(layer, config)?, false, false)
+    } else {
+        0
+    };
+    Ok(legend_width(config, label_x + label + config.theme.padding))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn entries(labels: &[&str]) -> Vec<(String, String)> {
+        labels
+            .iter()
+            .map(|l| (l.to_string(), "#000000".to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_vertical_rows() {
+        let layer: Layer = serde_json::from_value(json!({"id": "a", "type": "fill"})).unwrap();
+        let rows = RowLayout::new(
+            &layer,
+            &LegendConfig::default(),
+            &entries(&["x", "y", "z"]),
+            45,
+        )
+        .unwrap();
+        assert!(rows.title_row);
+        assert_eq!(rows.rows_height, 90);
+        assert_eq!(rows.position(2, 40), (0, 100));
+    }
+
+    #[test]
+    fn test_horizontal_rows_wrap_at_default_width() {
+        let layer: Layer = serde_json::from_value(json!({"id": "a", "type": "fill"})).unwrap();
+        let config = LegendConfig {
+            orientation: Orientation::Horizontal,
+            has_label: false,
+            ..Default::default()
+        };
+        // Entries are 35 + label wide ("park" 28, "wood" 34, "sand" 31), 15 apart: two fit
+        // in 200.
+        let rows =
+            RowLayout::new(&layer, &config, &entries(&["park", "wood", "sand"]), 45).unwrap();
+        assert!(!rows.title_row);
+        assert_eq!(rows.rows_height, 60);
+        assert_eq!(rows.position(0, 10), (0, 10));
+        assert_eq!(rows.position(1, 10), (78, 10));
+        assert_eq!(rows.position(2, 10), (0, 40));
+        assert_eq!(rows.width, 167);
+    }
+
+    #[test]
+    fn test_vertical_auto_width_fits_longest_label() {
+        let layer: Layer = serde_json::from_value(json!({"id": "a", "type": "fill"})).unwrap();
+        let labels = entries(&["x", "Residential and commercial areas"]);
+        let fixed = RowLayout::new(&layer, &LegendConfig::default(), &labels, 45).unwrap();
+        assert_eq!(fixed.width, 200);
+
+        let config = LegendConfig {
+            auto_width: true,
+            ..Default::default()
+        };
+        let auto = RowLayout::new(&layer, &config, &labels, 45).unwrap();
+        let longest = text_width(&config.theme, "Residential and commercial areas", false);
+        assert_eq!(auto.width, 45 + longest + 10);
+        assert!(auto.width > 200);
+
+        let config = LegendConfig {
+            max_width: Some(180),
+            ..config
+        };
+        assert_eq!(
+            RowLayout::new(&layer, &config, &labels, 45).unwrap().width,
+            180
+        );
+    }
+
+    #[test]
+    fn test_fit_label_wraps_on_words() {
+        let config = LegendConfig {
+            label_width: Some(80),
+            ..Default::default()
+        };
+        assert_eq!(fit_label(&config, "Park", false, true), vec!["Park"]);
+        let lines = fit_label(&config, "Residential and commercial areas", false, true);
+        assert!(lines.len() > 1);
+        assert_eq!(lines.join(" "), "Residential and commercial areas");
+        for line in &lines {
+            assert!(text_width(&config.theme, line, false) <= 80);
+        }
+        // A single word wider than the label is broken between characters.
+        let lines = fit_label(&config, "Donaudampfschifffahrt", false, true);
+        assert!(lines.len() > 1);
+        assert_eq!(lines.concat(), "Donaudampfschifffahrt");
+    }
+
+    #[test]
+    fn test_fit_label_truncates() {
+        let config = LegendConfig {
+            label_width: Some(80),
+            label_overflow: LabelOverflow::Truncate,
+            ..Default::default()
+        };
+        let lines = fit_label(&config, "Residential and commercial areas", false, true);
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].ends_with('…'));
+        assert!(text_width(&config.theme, &lines[0], false) <= 80);
+        // Rows that cannot grow truncate even in wrap mode.
+        let config = LegendConfig {
+            label_overflow: LabelOverflow::Wrap,
+            ..config
+        };
+        assert_eq!(
+            fit_label(&config, "Residential and commercial areas", true, false).len(),
+            1
+        );
+    }
+}
@@ -1,34 +1,59 @@
 // Modules of the crate containing specific logic for rendering different types of layers.
+mod accessibility;
+mod annotations;
 mod background;
 mod circle;
+mod color;
+mod columns;
 mod common;
+mod composite;
+mod dedupe;
 mod default;
+mod entries;
 mod error;
 mod fill;
 mod fill_extrusion;
 mod heatmap;
+mod layout;
 mod line;
+mod locale;
+mod metrics;
+mod numbers;
 mod raster;
 mod symbol;
+mod theme;
 
 #[cfg(all(feature = "async", feature = "sync"))]
 compile_error!("Features 'async' and 'sync' cannot be enabled at the same time.");
 
 // Imports of required functions and types from the modules.
 use crate::common::get_sprite;
+use accessibility::{describe_layer, with_description};
+use annotations::{attribution_block, layer_note, with_note, without_note};
 use background::render_background;
 use circle::render_circle;
-use common::{Layer, Style};
+pub use color::Color;
+use columns::{Block, FlowItem, RenderRows, Rows, compose, compose_lines, flow};
+pub use common::{Layer, Source, Style};
+use common::{get_layer_group, get_layer_label, render_separator, render_text};
+use composite::{composite_key, label_layer, render_composite};
+use dedupe::{SeenEntries, Unseen, retain_unseen};
 use default::render_default;
+use entries::layer_entries;
 pub use error::LegendError;
 use fill::render_fill;
 use fill_extrusion::render_fill_extrusion;
 use heatmap::render_heatmap;
 use image::DynamicImage;
+pub use layout::{LabelOverflow, Orientation};
+use layout::{legend_width, text_width};
 use line::render_line;
 use raster::render_raster;
 use serde_json::Value;
+use svg::Document;
 use symbol::render_symbol;
+pub use theme::LegendTheme;
+use theme::with_background;
 
 /// Configuration for a [`MapLibreLegend`] instance.
 ///
@@ -52,6 +77,64 @@ pub struct LegendConfig {
     pub has_label: bool,
     /// Whether to include `raster` layers in [`MapLibreLegend::render_all`]. Default: `false`.
     pub include_raster: bool,
+    /// Whether to describe layers without a `metadata.legend.label` by their `filter`
+    /// (e.g. `"class == park"`) instead of their id. Default: `false`.
+    pub label_from_filter: bool,
+    /// Whether [`MapLibreLegend::render_all`] groups layers without a
+    /// `metadata.legend.group` by their `source-layer`. Default: `false`.
+    pub group_by_source_layer: bool,
+    /// Whether [`MapLibreLegend::render_all`] draws single-color `fill`, `line` and `circle`
+    /// layers sharing `source-layer`, `filter` and zoom range as one composite swatch (e.g.
+    /// a road casing and the road). Layers can also opt in with `metadata.legend.composite`.
+    /// Default: `false`.
+    pub auto_composite: bool,
+    /// Whether [`MapLibreLegend::render_all`] skips legend rows identical to one already
+    /// drawn (same layer type, color and label), e.g. a class repeated across zoom-split
+    /// layers. The first occurrence keeps its place. Default: `false`.
+    pub dedupe_entries: bool,
+    /// Number of columns [`MapLibreLegend::render_all`] balances the layers over.
+    /// Default: `1`.
+    pub columns: u32,
+    /// Maximum height of a [`MapLibreLegend::render_all`] column; when set, new columns
+    /// are added as needed and [`LegendConfig::columns`] is ignored. Default: `None`.
+    pub max_height: Option<u32>,
+    /// Whether a layer's rows may be split across columns. When `false`, each layer
+    /// stays in one column. Default: `false`.
+    pub split_layers: bool,
+    /// Direction in which entries are laid out. [`Orientation::Horizontal`] places them
+    /// left to right, wrapping at `default_width`, with each layer title inline; layers then
+    /// report their content width, and [`MapLibreLegend::render_all`] flows them the same way
+    /// (ignoring `columns` and `max_height`). Default: [`Orientation::Vertical`].
+    pub orientation: Orientation,
+    /// Fonts, colors and spacing of the rendered legends. Default: [`LegendTheme::default`].
+    pub theme: LegendTheme,
+    /// Whether vertical legends are sized to their measured labels instead of
+    /// `default_width`. [`MapLibreLegend::render_all`] columns then take the width of their
+    /// widest layer. Default: `false`.
+    pub auto_width: bool,
+    /// Maximum width of legends sized to their content (with `auto_width`, or in
+    /// horizontal layouts); longer labels overflow it. Default: `None`.
+    pub max_width: Option<u32>,
+    /// Maximum width in pixels of a label; longer labels are wrapped or truncated as set by
+    /// [`LegendConfig::label_overflow`]. `None` leaves labels on one unbounded line.
+    /// Default: `None`.
+    pub label_width: Option<u32>,
+    /// How labels wider than [`LegendConfig::label_width`] are fitted.
+    /// Default: [`LabelOverflow::Wrap`].
+    pub label_overflow: LabelOverflow,
+    /// Overall title drawn above [`MapLibreLegend::render_all`]. Default: `None`.
+    pub title: Option<String>,
+    /// Whether [`MapLibreLegend::render_all`] is titled with the style's `name` when no
+    /// [`LegendConfig::title`] is set. Default: `false`.
+    pub title_from_style: bool,
+    /// Whether [`MapLibreLegend::render_all`] ends with a footer listing the attributions
+    /// of the style's `sources`. Default: `false`.
+    pub attribution: bool,
+    /// Locale of the labels, e.g. `"es"` or `"pt-BR"`. Selects localized `metadata.legend`
+    /// keys such as `label:es` or `custom-labels:pt-BR` (falling back to the language, then
+    /// to the plain key), and the language of generated words ("and", "has", ...) for
+    /// English, Spanish and Portuguese. Default: `None`.
+    pub locale: Option<String>,
 }
 
 impl Default for LegendConfig {
@@ -61,6 +144,23 @@ impl Default for LegendConfig {
             default_height: 40,
             has_label: true,
             include_raster: false,
+            label_from_filter: false,
+            group_by_source_layer: false,
+            auto_composite: false,
+            dedupe_entries: false,
+            columns: 1,
+            max_height: None,
+            split_layers: false,
+            orientation: Orientation::Vertical,
+            theme: LegendTheme::default(),
+            auto_width: false,
+            max_width: None,
+            label_width: None,
+            label_overflow: LabelOverflow::Wrap,
+            title: None,
+            title_from_style: false,
+            attribution: false,
+            locale: None,
         }
     }
 }
@@ -129,8 +229,17 @@ impl MapLibreLegend {
         })
     }
 
+    /// Returns the parsed style: its name, sources, layers and the other fields legends
+    /// are drawn from.
+    pub fn style(&self) -> &Style {
+        &self.style
+    }
+
     /// Renders a specific layer as an SVG string, identified by its ID.
     ///
+    /// The SVG has `role="img"`, a `<title>` with the layer label and a `<desc>` listing
+    /// its entries, and each entry's swatch and label are grouped under an `aria-label`.
+    ///
     /// # Parameters
     /// - `id`: The identifier of the layer to render.
     /// - `has_label`: Overrides [`LegendConfig::has_label`] for this call only.
@@ -141,25 +250,35 @@ impl MapLibreLegend {
     pub fn render_layer(&self, id: &str, has_label: Option<bool>) -> Result<String, LegendError> {
         let layer = self
             .style
-            .layers
-            .iter()
-            .find(|l| l.id == id)
+            .layer(id)
             .ok_or_else(|| LegendError::InvalidJson(format!("Layer with ID '{}' not found", id)))?;
-        let (svg, _, _) = render_layer_svg(
-            layer,
-            self.config.default_width,
-            self.config.default_height,
-            has_label.unwrap_or(self.config.has_label),
-            self.config.include_raster,
-            &self.sprite_data,
-        )?;
-        Ok(svg)
+        let config = LegendConfig {
+            has_label: has_label.unwrap_or(self.config.has_label),
+            ..self.config.clone()
+        };
+        let (svg, _, _) = render_layer_svg(layer, &config, &self.sprite_data, None)?;
+        let svg = with_background(svg, &self.config.theme);
+        let title = get_layer_label(layer, &config)?;
+        let description = describe_layer(layer, &config, &self.sprite_data, None)?;
+        Ok(with_description(svg, &title, &description).to_string())
     }
 
     /// Renders all layers in the style as a single combined SVG.
     ///
-    /// Layers are stacked vertically with separator lines between them. The resulting SVG
-    /// has a width equal to the maximum layer width and a height equal to the sum of layer heights.
+    /// Layers are stacked vertically with separator lines between them. Layers sharing a
+    /// `metadata.legend.group` (or `source-layer`, see [`LegendConfig::group_by_source_layer`])
+    /// form one section: a heading with the group name followed by the members' entries,
+    /// placed where the first member would appear. Layers forming a composite symbol (see
+    /// [`LegendConfig::auto_composite`]) are drawn as one swatch, and repeated rows are
+    /// dropped with [`LegendConfig::dedupe_entries`]. Layers flow over
+    /// [`LegendConfig::columns`] balanced columns (or columns of at most
+    /// [`LegendConfig::max_height`]) placed side by side; the resulting SVG is as wide as
+    /// the columns together and as tall as the tallest one. With
+    /// [`Orientation::Horizontal`], layers are placed left to right instead, wrapping at
+    /// `default_width`, with group headings inline.
+    ///
+    /// The SVG has `role="img"`, a `<title>` and a `<desc>` summarizing every layer, and
+    /// each layer, group heading and entry is a group with an `aria-label`.
     ///
     /// # Parameters
     /// - `rev`: If true, renders layers in reverse order.
@@ -168,71 +287,344 @@ impl MapLibreLegend {
     /// - `Result<String, LegendError>`: A string containing the combined SVG of all layers,
     ///   or a `LegendError` if any layer fails to render.
     pub fn render_all(&self, rev: bool) -> Result<String, LegendError> {
-        let mut combined_body = String::new();
-        let mut y_offset = 0;
-        let mut max_width = 0;
-        let total_layers = self.style.layers.len();
-
         // Create an iterator in normal or reversed order
-        let layer_iter: Box<dyn Iterator<Item = (usize, &Layer)>> = if rev {
-            Box::new(self.style.layers.iter().enumerate().rev())
+        let layer_iter: Box<dyn Iterator<Item = &Layer>> = if rev {
+            Box::new(self.style.layers.iter().rev())
         } else {
-            Box::new(self.style.layers.iter().enumerate())
+            Box::new(self.style.layers.iter())
         };
+        let sections = legend_sections(layer_iter, &self.config)?;
+        let mut seen = SeenEntries::new();
 
-        for (i, layer) in layer_iter {
-            let (svg, w, h) = render_layer_svg(
-                layer,
-                self.config.default_width,
-                self.config.default_height,
-                self.config.has_label,
-                self.config.include_raster,
-                &self.sprite_data,
-            )?;
-            let inner = svg
-                .lines()
-                .filter(|l| !l.contains("<svg") && !l.contains("</svg>"))
-                .collect::<Vec<_>>()
-                .join("\n");
-            max_width = max_width.max(w);
-            combined_body.push_str(&format!(
-                "<g transform='translate(0,{})'>{}\n</g>\n",
-                y_offset, inner
-            ));
-            let is_last = if rev { i == 0 } else { i == total_layers - 1 };
-            if !is_last {
-                combined_body.push_str(&format!(
-                    "<line x1='0' y1='{}' x2='{}' y2='{}' stroke='#333333' stroke-width='0.5'/>\n",
-                    y_offset + h,
-                    max_width,
-                    y_offset + h
-                ));
+        let mut items = Vec::new();
+        let mut descriptions = Vec::new();
+        for section in &sections {
+            let mut heading = section
+                .heading
+                .as_ref()
+                .map(|heading| heading_block(heading, &self.config));
+            let mut starts_section = true;
+            for (_, item) in &section.items {
+                let (block, rows) = if let [layer] = item.as_slice() {
+                    // Rows already drawn by an earlier layer are left out.
+                    let kept = if self.config.dedupe_entries {
+                        match retain_unseen(layer, &self.config, &self.sprite_data, &mut seen)? {
+                            Unseen::Nothing => continue,
+                            Unseen::Layer => None,
+                            Unseen::Rows(rows) => Some(rows),
+                        }
+                    } else {
+                        None
+                    };
+                    let config = LegendConfig {
+                        has_label: self.config.has_label
+                            && (section.heading.is_none() || !renders_entry_list(layer)),
+                        ..self.config.clone()
+                    };
+                    let (svg, width, height) =
+                        render_layer_svg(layer, &config, &self.sprite_data, kept.as_deref())?;
+                    // Skipped layers (e.g. an excluded raster) take no place in the legend.
+                    if width == 0 || height == 0 {
+                        continue;
+                    }
+                    descriptions.push(describe_layer(
+                        layer,
+                        &self.config,
+                        &self.sprite_data,
+                        kept.as_deref(),
+                    )?);
+                    let label = Some(get_layer_label(layer, &self.config)?);
+                    let rows = if self.config.split_layers {
+                        self.layer_rows((*layer).clone(), config, kept)?
+                    } else {
+                        None
+                    };
+                    let block = Block {
+                        svg,
+                        width,
+                        height,
+                        label,
+                    };
+                    (block, rows)
+                } else {
+                    // Composite members are drawn bottom-up, in style order.
+                    let mut members = item.clone();
+                    if rev {
+                        members.reverse();
+                    }
+                    let (svg, width, height) = render_composite(&members, &self.config)?;
+                    if width == 0 || height == 0 {
+                        continue;
+                    }
+                    let label = match label_layer(&members)? {
+                        Some(layer) => Some(get_layer_label(layer, &self.config)?),
+                        None => None,
+                    };
+                    descriptions.extend(label.clone());
+                    let block = Block {
+                        svg,
+                        width,
+                        height,
+                        label,
+                    };
+                    (block, None)
+                };
+                items.push(FlowItem {
+                    heading: heading.take(),
+                    starts_section,
+                    block,
+                    rows,
+                });
+                starts_section = false;
             }
-            y_offset += h;
         }
 
-        Ok(format!(
-            "<svg xmlns='http://www.w3.org/2000/svg' width='{w}' height='{h}' viewBox='0 0 {w} {h}'>\n{body}</svg>",
-            w = max_width,
-            h = y_offset,
-            body = combined_body
-        ))
+        let (doc, width, height) = match self.config.orientation {
+            Orientation::Vertical => compose(flow(items, &self.config)?, &self.config.theme),
+            Orientation::Horizontal => compose_lines(items, &self.config),
+        };
+        let title = self.config.title.as_deref().or(self
+            .style
+            .name
+            .as_deref()
+            .filter(|_| self.config.title_from_style));
+        let footer = if self.config.attribution {
+            attribution_block(&self.style.sources, width, &self.config)
+        } else {
+            None
+        };
+        let doc = if title.is_some() || footer.is_some() {
+            let body = Block {
+                svg: doc,
+                width,
+                height,
+                label: None,
+            };
+            let column = title
+                .map(|title| self.title_block(title, width))
+                .into_iter()
+                .chain([body])
+                .chain(footer)
+                .map(|block| (block, false))
+                .collect();
+            compose(vec![column], &self.config.theme).0
+        } else {
+            doc
+        };
+        let doc = with_background(doc, &self.config.theme);
+        let title = title.unwrap_or(locale::words(self.config.locale.as_deref()).legend);
+        Ok(with_description(doc, title, &descriptions.join("; ")).to_string())
+    }
+
+    /// Renders the overall title of [`MapLibreLegend::render_all`]: a heading on a row of
+    /// its own, in the theme's legend title font, spanning the legend `width`.
+    fn title_block(&self, title: &str, width: u32) -> Block {
+        let theme = LegendTheme {
+            title_font_size: self.config.theme.legend_title_font_size,
+            ..self.config.theme.clone()
+        };
+        let content = theme.padding + text_width(&theme, title, true) + theme.padding;
+        let config = LegendConfig {
+            orientation: Orientation::Vertical,
+            default_width: width.max(content),
+            auto_width: false,
+            theme,
+            ..self.config.clone()
+        };
+        heading_block(title, &config)
+    }
+
+    /// Prepares a multi-row layer to be split between rows across columns: each range of
+    /// its rows (`entries`, or all of its [`layer_entries`]) is rendered on its own, titled
+    /// only when it starts at the first row.
+    fn layer_rows(
+        &self,
+        layer: Layer,
+        config: LegendConfig,
+        entries: Option<Vec<(String, String)>>,
+    ) -> Result<Option<Rows<'_>>, LegendError> {
+        let entries = match entries {
+            Some(entries) => Some(entries),
+            None => layer_entries(&layer, &config, &self.sprite_data)?,
+        };
+        let entries = match entries {
+            Some(entries) if entries.len() > 1 => entries,
+            _ => return Ok(None),
+        };
+        let count = entries.len();
+        // The title is a single bold line above a separator; symbols keep their top padding
+        // below it, the other renderers reuse the space left below the last row.
+        let theme = &config.theme;
+        let title_height = if !config.has_label || config.orientation == Orientation::Horizontal {
+            0
+        } else if layer.layer_type == "symbol" {
+            theme.title_height()
+        } else {
+            theme.title_space()
+        };
+        let render: RenderRows = Box::new(move |range, with_title| {
+            // The note goes below the last rows only.
+            let layer = if range.end < count {
+                without_note(layer.clone())
+            } else {
+                layer.clone()
+            };
+            let config = LegendConfig {
+                has_label: config.has_label && with_title,
+                ..config.clone()
+            };
+            let (svg, width, height) =
+                render_layer_svg(&layer, &config, &self.sprite_data, Some(&entries[range]))?;
+            let label = Some(get_layer_label(&layer, &config)?);
+            Ok(Block {
+                svg,
+                width,
+                height,
+                label,
+            })
+        });
+        Ok(Some(Rows {
+            count,
+            title_height,
+            render,
+        }))
     }
 }
 
-/// Renders a single layer as an SVG based on its type and properties.
+/// A block of [`MapLibreLegend::render_all`]: a single ungrouped layer, or the members of
+/// a group rendered under a shared heading.
 ///
-/// Dispatches to the appropriate renderer based on `layer.layer_type`. Returns
-/// `(svg_string, width, height)`. Width and height are both `0` for skipped layers
+/// Each item is drawn as one block: a single layer, or several layers forming a composite
+/// symbol, keyed by their [`composite_key`].
+struct Section<'a> {
+    heading: Option<String>,
+    items: Vec<(Option<String>, Vec<&'a Layer>)>,
+}
+
+/// Splits layers into legend sections, keeping the order of first appearance. Members of a
+/// group are gathered into the section of the group's first layer, and members of a
+/// composite symbol into the item of its first layer.
+fn legend_sections<'a>(
+    layers: impl Iterator<Item = &'a Layer>,
+    config: &LegendConfig,
+) -> Result<Vec<Section<'a>>, LegendError> {
+    let mut sections: Vec<Section<'a>> = Vec::new();
+    for layer in layers {
+        let group = get_layer_group(layer, config)?;
+        let key = composite_key(layer, config)?;
+        // Composite members join their symbol wherever it is; other layers join their group.
+        let composite = key.as_ref().and_then(|k| {
+            sections.iter_mut().find_map(|s| {
+                s.items
+                    .iter_mut()
+                    .find(|(c, _)| c.as_ref() == Some(k))
+                    .map(|(_, members)| members)
+            })
+        });
+        if let Some(members) = composite {
+            members.push(layer);
+            continue;
+        }
+        match group
+            .as_ref()
+            .and_then(|g| sections.iter_mut().find(|s| s.heading.as_ref() == Some(g)))
+        {
+            Some(section) => section.items.push((key, vec![layer])),
+            None => sections.push(Section {
+                heading: group,
+                items: vec![(key, vec![layer])],
+            }),
+        }
+    }
+    Ok(sections)
+}
+
+/// Whether a layer renders as a titled list of entries rather than a single swatch with an
+/// inline label. Inside a group, the former drop their title (the group heading replaces
+/// it) while the latter keep their label, which is their only entry.
+fn renders_entry_list(layer: &Layer) -> bool {
+    match layer.layer_type.as_str() {
+        "fill" | "line" | "circle" => true,
+        "fill-extrusion" => layer.paint.as_ref().is_some_and(|p| p.is_object()),
+        "symbol" => layer
+            .layout
+            .as_ref()
+            .and_then(|l| l.get("icon-image"))
+            .is_some_and(|i| i.is_array()),
+        _ => false,
+    }
+}
+
+/// Renders a group heading: a bold title above a separator, or inline before the
+/// group's entries in horizontal layouts.
+fn heading_block(heading: &str, config: &LegendConfig) -> Block {
+    let theme = &config.theme;
+    let (width, height) = match config.orientation {
+        Orientation::Vertical => {
+            let content = theme.padding + text_width(theme, heading, true) + theme.padding;
+            (legend_width(config, content), theme.title_height())
+        }
+        Orientation::Horizontal => (
+            theme.padding + text_width(theme, heading, true),
+            config.default_height,
+        ),
+    };
+    let mut doc = Document::new().set("width", width).set("height", height);
+    match config.orientation {
+        Orientation::Vertical => {
+            render_text(
+                &mut doc,
+                theme,
+                heading,
+                theme.padding,
+                theme.title_baseline(),
+                true,
+            );
+            render_separator(&mut doc, theme, width, 0, theme.padding);
+        }
+        Orientation::Horizontal => {
+            let baseline = theme.baseline(theme.padding as i32);
+            render_text(&mut doc, theme, heading, theme.padding, baseline, true);
+        }
+    }
+    Block {
+        svg: doc,
+        width,
+        height,
+        label: Some(heading.to_string()),
+    }
+}
+
+/// Renders a single layer as an SVG based on its type and properties, with its
+/// `metadata.legend.note` below the entries.
+///
+/// `entries` replaces the layer's [`layer_entries`] when only some of them are drawn
+/// (rows already drawn by another layer, or split between columns).
+///
+/// Returns `(document, width, height)`. Width and height are both `0` for skipped layers
 /// (e.g. `raster` when `include_raster` is false).
 fn render_layer_svg(
     layer: &Layer,
-    def_w: u32,
-    def_h: u32,
-    render_label: bool,
-    include_raster: bool,
+    config: &LegendConfig,
     sprite_data: &[(DynamicImage, Value)],
-) -> Result<(String, u32, u32), LegendError> {
+    entries: Option<&[(String, String)]>,
+) -> Result<(Document, u32, u32), LegendError> {
+    let rendered = render_layer_type(layer, config, sprite_data, entries)?;
+    Ok(match layer_note(layer, config)? {
+        Some(note) if rendered.2 > 0 => with_note(rendered, note, config),
+        _ => rendered,
+    })
+}
+
+/// Renders a single layer as an SVG based on its type and properties.
+///
+/// Dispatches to the appropriate renderer based on `layer.layer_type`.
+fn render_layer_type(
+    layer: &Layer,
+    config: &LegendConfig,
+    sprite_data: &[(DynamicImage, Value)],
+    entries: Option<&[(String, String)]>,
+) -> Result<(Document, u32, u32), LegendError> {
     match layer.layer_type.as_str() {
         "fill" | "line" | "circle" => {
             let paint = layer
@@ -252,9 +644,9 @@ fn render_layer_svg(
                     ))
                 })?;
             match layer.layer_type.as_str() {
-                "fill" => render_fill(layer, paint, def_w, def_h, render_label),
-                "line" => render_line(layer, paint, def_w, def_h, render_label),
-                "circle" => render_circle(layer, paint, def_w, def_h, render_label),
+                "fill" => render_fill(layer, paint, config, sprite_data, entries),
+                "line" => render_line(layer, paint, config, sprite_data, entries),
+                "circle" => render_circle(layer, paint, config, entries),
                 _ => Err(LegendError::InvalidJson(format!(
                     "Unknown layer type '{}'",
                     layer.layer_type
@@ -265,21 +657,19 @@ fn render_layer_svg(
             // Paint is optional for these types; fall back to render_default if absent.
             if let Some(paint) = layer.paint.as_ref().and_then(|p| p.as_object()) {
                 match layer.layer_type.as_str() {
-                    "fill-extrusion" => {
-                        render_fill_extrusion(layer, paint, def_w, def_h, render_label)
-                    }
-                    "background" => render_background(layer, paint, def_w, def_h, render_label),
+                    "fill-extrusion" => render_fill_extrusion(layer, paint, config, entries),
+                    "background" => render_background(layer, paint, config, sprite_data),
                     _ => unreachable!(),
                 }
             } else {
-                render_default(layer, def_w, def_h, render_label)
+                render_default(layer, config)
             }
         }
-        "heatmap" => render_heatmap(layer, def_w, def_h, render_label),
-        "symbol" => render_symbol(layer, def_w, def_h, render_label, sprite_data),
-        "raster" if include_raster => render_raster(layer, def_w, def_h, render_label),
-        "raster" => Ok(("<svg></svg>".to_string(), 0, 0)),
-        _ => render_default(layer, def_w, def_h, render_label),
+        "heatmap" => render_heatmap(layer, config),
+        "symbol" => render_symbol(layer, config, sprite_data, entries),
+        "raster" if config.include_raster => render_raster(layer, config),
+        "raster" => Ok((Document::new(), 0, 0)),
+        _ => render_default(layer, config),
     }
 }
 
@@ -289,6 +679,19 @@ mod tests {
     use crate::common::Layer;
     use serde_json::json;
 
+    fn config(include_raster: bool) -> LegendConfig {
+        LegendConfig {
+            has_label: false,
+            include_raster,
+            ..Default::default()
+        }
+    }
+
+    /// Counts the text elements reading `text`.
+    fn drawn(svg: &str, text: &str) -> usize {
+        svg.matches(&format!("\n{}\n</text>", text)).count()
+    }
+
     fn fill_layer(id: &str, color: &str) -> Layer {
         serde_json::from_value(json!({
             "id": id,
@@ -302,7 +705,8 @@ mod tests {
     fn test_render_layer_svg_fill_single_color() {
         let layer = fill_layer("test", "#ff0000");
         // parse_expression for a plain color → 1 case → multi-case height = 50
-        let (svg, width, height) = render_layer_svg(&layer, 200, 40, false, false, &[]).unwrap();
+        let (svg, width, height) = render_layer_svg(&layer, &config(false), &[], None).unwrap();
+        let svg = svg.to_string();
         assert!(svg.contains("#ff0000"));
         assert_eq!(width, 200);
         assert_eq!(height, 50);
@@ -312,7 +716,8 @@ mod tests {
     fn test_render_layer_svg_unknown_type_uses_default() {
         let layer: Layer =
             serde_json::from_value(json!({"id": "x", "type": "custom-type"})).unwrap();
-        let (svg, _, _) = render_layer_svg(&layer, 200, 40, false, false, &[]).unwrap();
+        let (svg, _, _) = render_layer_svg(&layer, &config(false), &[], None).unwrap();
+        let svg = svg.to_string();
         // render_default uses the gray fallback color
         assert!(svg.contains("cccccc"));
     }
@@ -320,16 +725,18 @@ mod tests {
     #[test]
     fn test_render_layer_svg_raster_excluded_returns_empty() {
         let layer: Layer = serde_json::from_value(json!({"id": "r", "type": "raster"})).unwrap();
-        let (svg, width, height) = render_layer_svg(&layer, 200, 40, false, false, &[]).unwrap();
+        let (svg, width, height) = render_layer_svg(&layer, &config(false), &[], None).unwrap();
+        let svg = svg.to_string();
         assert_eq!(width, 0);
         assert_eq!(height, 0);
-        assert_eq!(svg, "<svg></svg>");
+        assert!(!svg.contains("<rect"));
     }
 
     #[test]
     fn test_render_layer_svg_raster_included() {
         let layer: Layer = serde_json::from_value(json!({"id": "r", "type": "raster"})).unwrap();
-        let (svg, width, height) = render_layer_svg(&layer, 200, 40, false, true, &[]).unwrap();
+        let (svg, width, height) = render_layer_svg(&layer, &config(true), &[], None).unwrap();
+        let svg = svg.to_string();
         assert_eq!(width, 200);
         assert!(height > 0);
         assert!(svg.contains("<svg"));
@@ -338,6 +745,415 @@ mod tests {
     #[test]
     fn test_render_layer_svg_fill_missing_paint_returns_err() {
         let layer: Layer = serde_json::from_value(json!({"id": "x", "type": "fill"})).unwrap();
-        assert!(render_layer_svg(&layer, 200, 40, false, false, &[]).is_err());
+        assert!(render_layer_svg(&layer, &config(false), &[], None).is_err());
+    }
+
+    fn legend(layers: Value, config: LegendConfig) -> MapLibreLegend {
+        MapLibreLegend {
+            style: serde_json::from_value(json!({ "layers": layers })).unwrap(),
+            config,
+            sprite_data: vec![],
+        }
+    }
+
+    fn grouped_fill(id: &str, color: &str, group: &str) -> Value {
+        json!({
+            "id": id, "type": "fill",
+            "paint": {"fill-color": color},
+            "metadata": {"legend": {"group": group}}
+        })
+    }
+
+    #[test]
+    fn test_render_all_skips_excluded_raster() {
+        let legend = legend(
+            json!([
+                {"id": "water", "type": "fill", "paint": {"fill-color": "#0000ff"}},
+                {"id": "r", "type": "raster"},
+                {"id": "parks", "type": "fill", "paint": {"fill-color": "#00ff00"}},
+            ]),
+            LegendConfig::default(),
+        );
+        let svg = legend.render_all(false).unwrap();
+        assert!(!svg.contains(r#"aria-label="r""#));
+        assert!(!svg.contains("raster layer"));
+        // A single divider, between water and parks.
+        assert_eq!(svg.matches("#333333").count(), 1);
+    }
+
+    #[test]
+    fn test_render_all_groups_members_under_heading() {
+        let legend = legend(
+            json!([
+                grouped_fill("parks", "#00ff00", "Land use"),
+                {"id": "water", "type": "fill", "paint": {"fill-color": "#0000ff"}},
+                grouped_fill("forest", "#008000", "Land use"),
+            ]),
+            LegendConfig::default(),
+        );
+        let svg = legend.render_all(false).unwrap();
+        // One heading for the group, placed before "water"; members lose their own titles.
+        assert_eq!(drawn(&svg, "Land use"), 1);
+        assert_eq!(drawn(&svg, "parks"), 1);
+        let heading = svg.find("Land use\n</text>").unwrap();
+        let forest = svg.find("#008000").unwrap();
+        let water = svg.find("#0000ff").unwrap();
+        assert!(heading < forest && forest < water);
+        // A divider between the two sections only.
+        assert_eq!(svg.matches("#333333").count(), 1);
+        // Heading (30) + two single-entry members (50 each) + titled water block (70).
+        assert!(svg.contains(r#"height="200""#));
+    }
+
+    #[test]
+    fn test_render_all_groups_by_source_layer() {
+        let legend = legend(
+            json!([
+                {"id": "a", "type": "fill", "source-layer": "landuse", "paint": {"fill-color": "#00ff00"}},
+                {"id": "b", "type": "fill", "source-layer": "landuse", "paint": {"fill-color": "#008000"}},
+            ]),
+            LegendConfig {
+                group_by_source_layer: true,
+                ..Default::default()
+            },
+        );
+        let svg = legend.render_all(false).unwrap();
+        assert_eq!(drawn(&svg, "landuse"), 1);
+        assert!(!svg.contains("#333333"));
+    }
+
+    #[test]
+    fn test_render_all_draws_composite_in_one_swatch() {
+        let legend = legend(
+            json!([
+                {"id": "road-casing", "type": "line", "source-layer": "transportation",
+                 "paint": {"line-color": "#555555", "line-width": 6}},
+                {"id": "water", "type": "fill", "paint": {"fill-color": "#0000ff"}},
+                {"id": "road", "type": "line", "source-layer": "transportation",
+                 "paint": {"line-color": "#ffcc00", "line-width": 4}},
+            ]),
+            LegendConfig {
+                auto_composite: true,
+                ..Default::default()
+            },
+        );
+        for rev in [false, true] {
+            let svg = legend.render_all(rev).unwrap();
+            assert!(!svg.contains("road-casing"));
+            // The casing is always drawn under the road.
+            assert!(svg.find("#555555").unwrap() < svg.find("#ffcc00").unwrap());
+            // Composite (40) + water block (70).
+            assert!(svg.contains(r#"height="110""#));
+        }
+    }
+
+    #[test]
+    fn test_render_all_dedupes_entries_across_layers() {
+        let landuse = |id: &str| {
+            json!({
+                "id": id, "type": "fill",
+                "paint": {"fill-color": ["match", ["get", "class"], "park", "#00ff00", "#cccccc"]},
+                "metadata": {"legend": {"label": "Land use", "default": "Other"}}
+            })
+        };
+        let layers = json!([landuse("landuse-z10"), landuse("landuse-z14")]);
+        let config = LegendConfig {
+            dedupe_entries: true,
+            ..Default::default()
+        };
+        let svg = legend(layers.clone(), config).render_all(false).unwrap();
+        assert_eq!(svg.matches("#00ff00").count(), 1);
+        assert!(!svg.contains("#333333"));
+        let svg = legend(layers, LegendConfig::default())
+            .render_all(false)
+            .unwrap();
+        assert_eq!(svg.matches("#00ff00").count(), 2);
+    }
+
+    #[test]
+    fn test_render_all_dedupe_keeps_layer_paint() {
+        let roads = |id: &str, width: u32| {
+            json!({
+                "id": id, "type": "line",
+                "paint": {
+                    "line-color": ["match", ["get", "class"], "primary", "#ff0000", "minor", "#0000ff", "#cccccc"],
+                    "line-width": width,
+                    "line-dasharray": [2, 1]
+                },
+                "metadata": {"legend": {"default": "Other"}}
+            })
+        };
+        let layers = json!([roads("roads", 2), roads("roads-wide", 7)]);
+        let config = LegendConfig {
+            dedupe_entries: true,
+            ..Default::default()
+        };
+        let svg = legend(layers, config).render_all(false).unwrap();
+        // Every row of the second layer repeats, so it is dropped entirely.
+        assert!(!svg.contains(r#"stroke-width="7""#));
+
+        let layers = json!([roads("roads", 2), {
+            "id": "paths", "type": "line",
+            "paint": {
+                "line-color": ["match", ["get", "class"], "primary", "#ff0000", "path", "#00aa00", "#999999"],
+                "line-width": 7,
+                "line-dasharray": [2, 1]
+            }
+        }]);
+        let config = LegendConfig {
+            dedupe_entries: true,
+            ..Default::default()
+        };
+        let svg = legend(layers, config).render_all(false).unwrap();
+        assert_eq!(svg.matches("#ff0000").count(), 1);
+        // The remaining rows keep the layer's own width and dashes.
+        assert_eq!(svg.matches(r#"stroke-width="7""#).count(), 2);
+        assert!(svg.contains("#00aa00"));
+    }
+
+    fn fills(n: usize) -> Value {
+        (0..n)
+            .map(|i| json!({"id": format!("f{}", i), "type": "fill", "paint": {"fill-color": "#ff0000"}}))
+            .collect()
+    }
+
+    #[test]
+    fn test_render_all_balances_columns() {
+        let config = LegendConfig {
+            columns: 2,
+            ..Default::default()
+        };
+        let svg = legend(fills(4), config).render_all(false).unwrap();
+        assert!(svg.contains(r#"viewBox="0 0 400 140""#));
+        assert!(svg.contains("translate(200,70)"));
+    }
+
+    #[test]
+    fn test_render_all_max_height() {
+        let config = LegendConfig {
+            max_height: Some(150),
+            ..Default::default()
+        };
+        let svg = legend(fills(5), config).render_all(false).unwrap();
+        assert!(svg.contains(r#"viewBox="0 0 600 140""#));
+    }
+
+    #[test]
+    fn test_render_all_splits_layer_rows_when_allowed() {
+        let layers = json!([{
+            "id": "landuse", "type": "fill",
+            "paint": {"fill-color": ["match", ["get", "class"],
+                "a", "#000001", "b", "#000002", "c", "#000003",
+                "d", "#000004", "e", "#000005", "#000006"]}
+        }]);
+        let config = LegendConfig {
+            columns: 2,
+            ..Default::default()
+        };
+        // Kept whole: one column of 20 + 6 * 30 + 20.
+        let svg = legend(layers.clone(), config.clone())
+            .render_all(false)
+            .unwrap();
+        assert!(svg.contains(r#"viewBox="0 0 200 220""#));
+
+        let config = LegendConfig {
+            split_layers: true,
+            ..config
+        };
+        let svg = legend(layers, config).render_all(false).unwrap();
+        assert!(svg.contains(r#"viewBox="0 0 400 130""#));
+        assert_eq!(drawn(&svg, "landuse"), 2);
+        for i in 1..=6 {
+            assert_eq!(svg.matches(&format!("#00000{}", i)).count(), 1);
+        }
+    }
+
+    #[test]
+    fn test_layer_rows_title_height_matches_rendering() {
+        let layers = json!([
+            {"id": "landuse", "type": "fill",
+             "paint": {"fill-color": ["match", ["get", "class"], "a", "#000001", "#000002"]},
+             "metadata": {"legend": {"note": "Source: survey"}}},
+            {"id": "stops", "type": "circle",
+             "paint": {"circle-color": ["match", ["get", "kind"], "bus", "#000003", "#000004"]}}
+        ]);
+        let config = LegendConfig {
+            split_layers: true,
+            ..Default::default()
+        };
+        let legend = legend(layers, config.clone());
+        for layer in &legend.style.layers {
+            let rows = legend
+                .layer_rows(layer.clone(), config.clone(), None)
+                .unwrap()
+                .unwrap();
+            let titled = (rows.render)(0..rows.count, true).unwrap().height;
+            let untitled = (rows.render)(0..rows.count, false).unwrap().height;
+            assert_eq!(rows.title_height, titled - untitled);
+        }
+    }
+
+    #[test]
+    fn test_render_all_horizontal_wraps_layers() {
+        let config = LegendConfig {
+            orientation: Orientation::Horizontal,
+            default_width: 250,
+            ..Default::default()
+        };
+        // Each fill is an inline title and one entry, 95 wide: two per line.
+        let svg = legend(fills(3), config).render_all(false).unwrap();
+        assert!(svg.contains("translate(95,0)"));
+        assert!(svg.contains("translate(0,50)"));
+        assert!(svg.contains(r#"viewBox="0 0 190 100""#));
+    }
+
+    #[test]
+    fn test_render_all_applies_theme() {
+        let config = LegendConfig {
+            theme: LegendTheme {
+                font_family: Some("Inter".to_string()),
+                text_color: "#1a1a1a".to_string(),
+                title_font_size: 18,
+                divider_color: "#0055aa".to_string(),
+                background: Some("#fafafa".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let svg = legend(fills(2), config).render_all(false).unwrap();
+        assert!(svg.contains("font-family=\"Inter\""));
+        assert!(svg.contains("fill=\"#1a1a1a\""));
+        assert!(!svg.contains("fill=\"black\""));
+        assert!(svg.contains("font-size=\"18\""));
+        assert!(svg.contains("stroke=\"#0055aa\""));
+        // The background goes first, behind every layer.
+        let background = svg.find("fill=\"#fafafa\"").unwrap();
+        assert!(background < svg.find("<g").unwrap());
+    }
+
+    #[test]
+    fn test_render_all_auto_width_uses_widest_layer() {
+        let config = LegendConfig {
+            auto_width: true,
+            ..Default::default()
+        };
+        let svg = legend(fills(2), config).render_all(false).unwrap();
+        // Title and entry "f0" fit in well under the default 200.
+        let (_, width, _) = render_layer_svg(
+            &fill_layer("f0", "#ff0000"),
+            &LegendConfig {
+                auto_width: true,
+                ..Default::default()
+            },
+            &[],
+            None,
+        )
+        .unwrap();
+        assert!(width < 100);
+        assert!(svg.contains(&format!(r#"viewBox="0 0 {} 140""#, width)));
+    }
+
+    #[test]
+    fn test_render_all_keeps_labels_mentioning_svg() {
+        let layers = json!([{
+            "id": "a", "type": "fill", "paint": {"fill-color": "#ff0000"},
+            "metadata": {"legend": {"label": "<svg> tiles"}}
+        }]);
+        let svg = legend(layers, LegendConfig::default())
+            .render_all(false)
+            .unwrap();
+        assert!(svg.contains("&lt;svg&gt; tiles"));
+        assert_eq!(svg.matches("<svg").count(), 1);
+        assert!(svg.contains("#ff0000"));
+    }
+
+    #[test]
+    fn test_render_output_is_accessible() {
+        let layers = json!([{
+            "id": "landuse", "type": "fill",
+            "paint": {"fill-color": ["match", ["get", "class"], "park", "#00ff00", "#cccccc"]},
+            "metadata": {"legend": {"label": "Land use", "default": "Other"}}
+        }, {
+            "id": "water", "type": "fill", "paint": {"fill-color": "#0000ff"}
+        }]);
+        let legend = legend(layers, LegendConfig::default());
+
+        let svg = legend.render_layer("landuse", None).unwrap();
+        assert!(svg.contains(r#"role="img""#));
+        assert!(svg.contains("<title>Land use</title>"));
+        assert!(svg.contains("Land use (fill layer): park, Other"));
+        assert!(svg.contains(r#"aria-label="park""#));
+        assert!(svg.contains(r#"aria-label="Other""#));
+
+        let svg = legend.render_all(false).unwrap();
+        assert_eq!(svg.matches(r#"role="img""#).count(), 1);
+        assert!(svg.contains("<title>Legend</title>"));
+        assert!(svg.contains("Land use (fill layer): park, Other; water (fill layer)"));
+        assert!(svg.contains(r#"aria-label="Land use""#));
+        assert!(svg.contains(r#"aria-label="water""#));
+    }
+
+    #[test]
+    fn test_render_all_title_notes_and_attribution() {
+        let style = json!({
+            "name": "City map",
+            "sources": {"osm": {"type": "vector", "attribution": "&copy; OpenStreetMap"}},
+            "layers": [{
+                "id": "water", "type": "fill", "paint": {"fill-color": "#0000ff"},
+                "metadata": {"legend": {"note": "Seasonal lakes"}}
+            }]
+        });
+        let legend = |config| MapLibreLegend {
+            style: serde_json::from_value(style.clone()).unwrap(),
+            config,
+            sprite_data: vec![],
+        };
+
+        // The note is drawn under the entries, even without a title or footer.
+        let svg = legend(LegendConfig::default()).render_all(false).unwrap();
+        assert!(svg.contains("Seasonal lakes"));
+        assert!(!svg.contains("City map"));
+        assert!(!svg.contains("OpenStreetMap"));
+        // Titled block (70) + one note line (17).
+        assert!(svg.contains(r#"viewBox="0 0 200 87""#));
+
+        let config = LegendConfig {
+            title_from_style: true,
+            attribution: true,
+            ..Default::default()
+        };
+        let svg = legend(config).render_all(false).unwrap();
+        assert!(svg.contains("<title>City map</title>"));
+        assert_eq!(drawn(&svg, "City map"), 1);
+        assert!(svg.contains(r#"font-size="18""#));
+        assert_eq!(drawn(&svg, "© OpenStreetMap"), 1);
+        let title = svg.find("City map\n</text>").unwrap();
+        let water = svg.find("#0000ff").unwrap();
+        let footer = svg.find("© OpenStreetMap\n</text>").unwrap();
+        assert!(title < water && water < footer);
+        // Title row (33 at 18 px) + layer (87) + one footer line (27).
+        assert!(svg.contains(r#"viewBox="0 0 200 147""#));
+
+        let config = LegendConfig {
+            title: Some("Legend of the city".to_string()),
+            title_from_style: true,
+            ..Default::default()
+        };
+        let svg = legend(config).render_all(false).unwrap();
+        assert!(svg.contains("Legend of the city"));
+        assert!(!svg.contains("City map"));
+    }
+
+    #[test]
+    fn test_style_accessor() {
+        let legend = legend(
+            json!([{"id": "water", "type": "fill", "source": "osm", "minzoom": 4}]),
+            LegendConfig::default(),
+        );
+        let style = legend.style();
+        assert_eq!(style.layers.len(), 1);
+        assert_eq!(style.layer("water").unwrap().source.as_deref(), Some("osm"));
+        assert_eq!(style.layers[0].minzoom, Some(4.0));
+        assert!(style.sources.is_empty());
     }
 }
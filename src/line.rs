@@ -1,35 +1,80 @@
 use crate::{
+    LegendConfig, LegendTheme,
+    color::Color,
     common::{
-        FONT_SIZE, Layer, PADDING, ROW_HEIGHT, extract_color, parse_expression, render_label,
-        render_separator,
+        FALLBACK_COLOR, Layer, constant_color, extract_color, get_fill_and_opacity,
+        get_layer_label, get_sprite_icon, has_sprite_icon, parse_expression, pattern_cases,
+        render_label, svg_id,
     },
     error::LegendError,
+    layout::{RowLayout, inline_width},
 };
+use image::DynamicImage;
+use serde_json::{Value, json};
 use svg::Document;
-use svg::node::element::{Line, Text as SvgText};
+use svg::node::element::{Definitions, Image, Line, LinearGradient, Pattern, Stop};
 
 /// Renders a `line` layer legend as an SVG.
 ///
 /// - Single-color paint: one horizontal line segment with the layer label alongside.
-/// - Expression-based paint: one line per case, stacked vertically with labels.
+/// - Expression-based paint: one line per case with its label, laid out by [`RowLayout`].
+/// - `line-pattern`: the sprite image is repeated along the line, one line per case.
+/// - `line-gradient`: a single line stroked with the gradient over `line-progress`.
 ///
+/// Paint precedence follows MapLibre: `line-pattern`, then `line-gradient`, then `line-color`.
+/// A pattern missing from the sprite (or `""`) and a gradient over an input other than
+/// `line-progress` fall back to `line-color`.
 /// Respects `line-width`, `line-opacity`, `line-dasharray`, and the layout property `line-cap`.
+/// Given `entries` replace the rows parsed from `line-pattern` or `line-color`.
 ///
-/// Returns `(svg_string, width, height)`.
+/// Returns `(document, width, height)`.
 pub fn render_line(
     layer: &Layer,
-    paint: &serde_json::Map<String, serde_json::Value>,
-    default_width: u32,
-    default_height: u32,
-    has_label: bool,
-) -> Result<(String, u32, u32), LegendError> {
-    let color_expr = paint.get("line-color").ok_or_else(|| {
-        LegendError::InvalidJson(format!(
-            "Layer '{}': missing 'line-color' in paint",
-            layer.id
-        ))
-    })?;
-    let cases = parse_expression(layer, color_expr)?;
+    paint: &serde_json::Map<String, Value>,
+    config: &LegendConfig,
+    sprite_data: &[(DynamicImage, Value)],
+    entries: Option<&[(String, String)]>,
+) -> Result<(Document, u32, u32), LegendError> {
+    let &LegendConfig {
+        default_height,
+        has_label,
+        ref theme,
+        ..
+    } = config;
+    let pattern_cases = match paint.get("line-pattern") {
+        Some(expr) => pattern_cases(layer, config, expr, sprite_data)?,
+        None => None,
+    };
+    let gradient = match paint.get("line-gradient") {
+        Some(expr) if pattern_cases.is_none() => parse_gradient_stops(layer, expr)?,
+        _ => None,
+    };
+    // Without a drawable pattern or gradient, the line is drawn with `line-color`.
+    let fallback_expr = Value::String(FALLBACK_COLOR.to_string());
+    let color_expr = match paint.get("line-color") {
+        Some(color) => color,
+        None if paint.contains_key("line-pattern") || paint.contains_key("line-gradient") => {
+            &fallback_expr
+        }
+        None => {
+            return Err(LegendError::InvalidJson(format!(
+                "Layer '{}': missing 'line-color' in paint",
+                layer.id
+            )));
+        }
+    };
+    // Rows whose pattern is missing from the sprite are drawn with the line color.
+    let fallback_color =
+        constant_color(Some(color_expr)).unwrap_or_else(|| FALLBACK_COLOR.to_string());
+    let cases = if gradient.is_some() {
+        vec![(get_layer_label(layer, config)?, String::new())]
+    } else if let Some(entries) = entries {
+        entries.to_vec()
+    } else if let Some(cases) = &pattern_cases {
+        cases.clone()
+    } else {
+        parse_expression(layer, config, color_expr)?
+    };
 
     let line_width = paint
         .get("line-width")
@@ -67,64 +112,79 @@ pub fn render_line(
         .unwrap_or("butt")
         .to_string();
 
-    let mut init_y = ROW_HEIGHT as i32;
+    // Sample lines run through the middle of each row.
+    let rows = RowLayout::new(layer, config, &cases, theme.label_x())?;
+    let mut init_y = theme.row_height as i32;
     let dynamic_height = if cases.is_empty() {
         0
     } else {
-        ROW_HEIGHT / 3 * 2 + cases.len() as u32 * ROW_HEIGHT
+        theme.swatch_height + rows.rows_height
     };
     let height = if !cases.is_empty() {
-        if has_label {
-            init_y += ROW_HEIGHT as i32;
-            dynamic_height + ROW_HEIGHT / 3 * 2
+        if rows.title_row {
+            init_y += theme.title_height() as i32;
+            dynamic_height + theme.title_space()
         } else {
             dynamic_height
         }
     } else {
         default_height
     };
+    let width = if cases.is_empty() {
+        inline_width(layer, config, theme.inline_label_x())?
+    } else {
+        rows.width
+    };
 
-    let mut doc = Document::new()
-        .set("width", default_width)
-        .set("height", height);
+    let mut doc = Document::new().set("width", width).set("height", height);
 
     if !cases.is_empty() {
         if has_label {
-            render_label(layer, &mut doc, Some(10), Some(20), Some(true))?;
-            render_separator(&mut doc, default_width, 0, 10);
+            rows.render_title(layer, config, &mut doc, init_y + theme.text_offset())?;
         }
-        for (i, (label, color)) in cases.iter().enumerate() {
-            let y = init_y + i as i32 * ROW_HEIGHT as i32;
+        for (i, (_, color)) in cases.iter().enumerate() {
+            let (dx, y) = rows.position(i, init_y);
+            let (stroke, stroke_opacity) = if let Some(stops) = &gradient {
+                let id = svg_id(layer, &format!("gradient-{}", i));
+                doc = doc.add(Definitions::new().add(line_gradient(theme, stops, &id, dx, y)));
+                (format!("url(#{})", id), opacity)
+            } else if pattern_cases.is_some() && has_sprite_icon(sprite_data, color) {
+                let id = svg_id(layer, &format!("pattern-{}", i));
+                let pattern = line_pattern(theme, sprite_data, color, &id, dx, y, line_width)?;
+                doc = doc.add(Definitions::new().add(pattern));
+                (format!("url(#{})", id), opacity)
+            } else if pattern_cases.is_some() {
+                get_fill_and_opacity(&fallback_color, opacity)
+            } else {
+                get_fill_and_opacity(color, opacity)
+            };
             let mut line = Line::new()
-                .set("x1", PADDING)
+                .set("x1", theme.padding + dx)
                 .set("y1", y)
-                .set("x2", 40)
+                .set("x2", theme.padding + theme.swatch_width + dx)
                 .set("y2", y)
-                .set("stroke", color.as_str())
+                .set("stroke", stroke)
                 .set("stroke-width", line_width)
-                .set("stroke-opacity", opacity)
+                .set("stroke-opacity", stroke_opacity)
                 .set("stroke-linecap", linecap.as_str());
             if let Some(ref da) = dasharray {
                 line = line.set("stroke-dasharray", da.as_str());
             }
-            let text = SvgText::new("")
-                .set("x", 45)
-                .set("y", y + 5)
-                .set("font-size", FONT_SIZE)
-                .set("fill", "black")
-                .add(svg::node::Text::new(label.clone()));
-            doc = doc.add(line).add(text);
+            let baseline = y + theme.text_offset();
+            rows.render_entry(theme, &mut doc, i, line, theme.label_x() + dx, baseline);
         }
     } else {
         let color = extract_color(Some(color_expr))?;
+        let (stroke, stroke_opacity) = get_fill_and_opacity(&color, opacity);
+        let center_y = theme.padding + theme.swatch_height / 2;
         let mut line = Line::new()
-            .set("x1", PADDING)
-            .set("y1", 20)
-            .set("x2", 40)
-            .set("y2", 20)
-            .set("stroke", color)
+            .set("x1", theme.padding)
+            .set("y1", center_y)
+            .set("x2", theme.padding + theme.swatch_width)
+            .set("y2", center_y)
+            .set("stroke", stroke)
             .set("stroke-width", line_width)
-            .set("stroke-opacity", opacity)
+            .set("stroke-opacity", stroke_opacity)
             .set("stroke-linecap", linecap.as_str());
         if let Some(ref da) = dasharray {
             line = line.set("stroke-dasharray", da.as_str());
@@ -132,11 +192,159 @@ pub fn render_line(
         doc = doc.add(line);
 
         if has_label {
-            render_label(layer, &mut doc, None, None, None)?;
+            render_label(layer, config, &mut doc, None, None, None)?;
         }
     }
 
-    Ok((doc.to_string(), default_width, height))
+    Ok((doc, width, height))
+}
+
+/// Returns whether a `line-gradient` value is an `interpolate` or `step` over
+/// `["line-progress"]`, the only input that can be drawn along the sample line.
+pub fn is_progress_gradient(expr: &Value) -> bool {
+    let Some(arr) = expr.as_array() else {
+        return false;
+    };
+    let input = match arr.first().and_then(|v| v.as_str()) {
+        Some("interpolate") => arr.get(2),
+        Some("step") => arr.get(1),
+        _ => None,
+    };
+    input == Some(&json!(["line-progress"]))
+}
+
+/// Extracts `(offset, color)` gradient stops from a `line-gradient` expression.
+///
+/// Supports `["interpolate", interp, ["line-progress"], p0, c0, ...]` (smooth stops) and
+/// `["step", ["line-progress"], c0, p1, c1, ...]` (hard color changes at each threshold).
+/// Returns `None` for an input other than `["line-progress"]`, which has no place along
+/// the sample line; the line is then drawn solid.
+fn parse_gradient_stops(
+    layer: &Layer,
+    expr: &Value,
+) -> Result<Option<Vec<(f64, String)>>, LegendError> {
+    let invalid = |msg: &str| {
+        LegendError::InvalidExpression(format!("Layer '{}': {} in 'line-gradient'", layer.id, msg))
+    };
+    let arr = expr
+        .as_array()
+        .ok_or_else(|| invalid("expected an expression"))?;
+    let color_at = |i: usize| -> Result<String, LegendError> {
+        extract_color(arr.get(i)).map_err(|_| invalid("missing color"))
+    };
+    let offset_at = |i: usize| -> Result<f64, LegendError> {
+        arr.get(i)
+            .and_then(|v| v.as_f64())
+            .ok_or_else(|| invalid("stop is not a number"))
+    };
+
+    if !matches!(
+        arr.first().and_then(|v| v.as_str()),
+        Some("interpolate" | "step")
+    ) {
+        return Err(invalid("expected 'interpolate' or 'step'"));
+    }
+    if !is_progress_gradient(expr) {
+        return Ok(None);
+    }
+
+    let mut stops = Vec::new();
+    match arr.first().and_then(|v| v.as_str()) {
+        Some("interpolate") => {
+            let mut i = 3;
+            while i + 1 < arr.len() {
+                stops.push((offset_at(i)?, color_at(i + 1)?));
+                i += 2;
+            }
+        }
+        Some("step") => {
+            let mut color = color_at(2)?;
+            stops.push((0.0, color.clone()));
+            let mut i = 3;
+            while i + 1 < arr.len() {
+                let offset = offset_at(i)?;
+                stops.push((offset, color));
+                color = color_at(i + 1)?;
+                stops.push((offset, color.clone()));
+                i += 2;
+            }
+            stops.push((1.0, color));
+        }
+        _ => return Err(invalid("expected 'interpolate' or 'step'")),
+    }
+    if stops.is_empty() {
+        return Err(invalid("no stops"));
+    }
+    Ok(Some(stops))
+}
+
+/// Builds a horizontal gradient spanning the sample line at height `y`, shifted right by `dx`.
+///
+/// Uses `userSpaceOnUse` units because a horizontal line has a zero-height bounding box,
+/// which would make an `objectBoundingBox` gradient invisible.
+fn line_gradient(
+    theme: &LegendTheme,
+    stops: &[(f64, String)],
+    id: &str,
+    dx: u32,
+    y: i32,
+) -> LinearGradient {
+    let mut gradient = LinearGradient::new()
+        .set("id", id)
+        .set("gradientUnits", "userSpaceOnUse")
+        .set("x1", theme.padding + dx)
+        .set("y1", y)
+        .set("x2", theme.padding + theme.swatch_width + dx)
+        .set("y2", y);
+    for (offset, color) in stops {
+        let (stop_color, stop_opacity) = match Color::parse(color) {
+            Some(c) => (c.to_hex(), c.a),
+            None => (color.clone(), 1.0),
+        };
+        gradient = gradient.add(
+            Stop::new()
+                .set("offset", format!("{}%", offset.clamp(0.0, 1.0) * 100.0))
+                .set("stop-color", stop_color)
+                .set("stop-opacity", stop_opacity),
+        );
+    }
+    gradient
+}
+
+/// Builds a pattern that repeats the sprite icon along the sample line at height `y`,
+/// shifted right by `dx`.
+///
+/// The icon is scaled so that its height matches the line width, as MapLibre does.
+fn line_pattern(
+    theme: &LegendTheme,
+    sprites: &[(DynamicImage, Value)],
+    icon_name: &str,
+    id: &str,
+    dx: u32,
+    y: i32,
+    line_width: f64,
+) -> Result<Pattern, LegendError> {
+    let icon = get_sprite_icon(sprites, icon_name)?;
+    let tile_h = line_width.max(1.0);
+    let tile_w = if icon.height > 0.0 {
+        icon.width * tile_h / icon.height
+    } else {
+        tile_h
+    };
+    Ok(Pattern::new()
+        .set("id", id)
+        .set("patternUnits", "userSpaceOnUse")
+        .set("x", theme.padding + dx)
+        .set("y", y as f64 - tile_h / 2.0)
+        .set("width", tile_w)
+        .set("height", tile_h)
+        .add(
+            Image::new()
+                .set("width", tile_w)
+                .set("height", tile_h)
+                .set("preserveAspectRatio", "none")
+                .set("href", icon.data_url),
+        ))
 }
 
 #[cfg(test)]
@@ -145,6 +353,13 @@ mod tests {
     use crate::common::Layer;
     use serde_json::json;
 
+    fn config(has_label: bool) -> LegendConfig {
+        LegendConfig {
+            has_label,
+            ..Default::default()
+        }
+    }
+
     fn make_layer(id: &str) -> Layer {
         serde_json::from_value(json!({"id": id, "type": "line"})).unwrap()
     }
@@ -162,8 +377,9 @@ mod tests {
         let layer = make_layer("test");
         let p = paint(json!({"line-color": "#ff0000"}));
         // parse_expression returns 1 entry for plain string → multi-case path:
-        // height = ROW_HEIGHT/3*2(20) + 1 * ROW_HEIGHT(30) = 50
-        let (svg, width, height) = render_line(&layer, &p, 200, 40, false).unwrap();
+        // height = swatch_height(20) + 1 * row_height(30) = 50
+        let (svg, width, height) = render_line(&layer, &p, &config(false), &[], None).unwrap();
+        let svg = svg.to_string();
         assert_eq!(width, 200);
         assert_eq!(height, 50);
         assert!(svg.contains("#ff0000"));
@@ -175,7 +391,8 @@ mod tests {
         let p = paint(json!({
             "line-color": ["match", ["get", "tipo"], "prim", "#ff0000", "#cccccc"]
         }));
-        let (svg, _, _) = render_line(&layer, &p, 200, 40, false).unwrap();
+        let (svg, _, _) = render_line(&layer, &p, &config(false), &[], None).unwrap();
+        let svg = svg.to_string();
         assert!(svg.contains("#ff0000"));
         assert!(svg.contains("prim"));
     }
@@ -184,16 +401,28 @@ mod tests {
     fn test_render_line_dasharray_in_svg() {
         let layer = make_layer("test");
         let p = paint(json!({"line-color": "#000000", "line-dasharray": [4, 2]}));
-        let (svg, _, _) = render_line(&layer, &p, 200, 40, false).unwrap();
+        let (svg, _, _) = render_line(&layer, &p, &config(false), &[], None).unwrap();
+        let svg = svg.to_string();
         assert!(svg.contains("stroke-dasharray"));
         assert!(svg.contains('4'));
     }
 
+    #[test]
+    fn test_render_line_rgba_color_folds_alpha() {
+        let layer = make_layer("test");
+        let p = paint(json!({"line-color": "rgba(255, 0, 0, 0.5)", "line-opacity": 0.5}));
+        let (svg, _, _) = render_line(&layer, &p, &config(false), &[], None).unwrap();
+        let svg = svg.to_string();
+        assert!(svg.contains(r##"stroke="#ff0000""##));
+        assert!(svg.contains(r#"stroke-opacity="0.25""#));
+    }
+
     #[test]
     fn test_render_line_linecap_from_layout() {
         let layer = make_layer_with_layout("test", json!({"line-cap": "round"}));
         let p = paint(json!({"line-color": "#000000"}));
-        let (svg, _, _) = render_line(&layer, &p, 200, 40, false).unwrap();
+        let (svg, _, _) = render_line(&layer, &p, &config(false), &[], None).unwrap();
+        let svg = svg.to_string();
         assert!(svg.contains("round"));
     }
 
@@ -201,6 +430,82 @@ mod tests {
     fn test_render_line_missing_color_returns_err() {
         let layer = make_layer("test");
         let p = paint(json!({}));
-        assert!(render_line(&layer, &p, 200, 40, false).is_err());
+        assert!(render_line(&layer, &p, &config(false), &[], None).is_err());
+    }
+
+    #[test]
+    fn test_render_line_pattern() {
+        let layer = make_layer("trail");
+        let p = paint(json!({"line-pattern": "dash", "line-width": 4}));
+        let sprites = vec![(
+            DynamicImage::new_rgba8(8, 2),
+            json!({"dash": {"x": 0, "y": 0, "width": 8, "height": 2}}),
+        )];
+        let (svg, _, _) = render_line(&layer, &p, &config(false), &sprites, None).unwrap();
+        let svg = svg.to_string();
+        assert!(svg.contains("<pattern"));
+        assert!(svg.contains("url(#trail-pattern-0)"));
+        // 8x2 icon scaled to the 4 px line width → 16x4 tile
+        assert!(svg.contains(r#"width="16""#));
+    }
+
+    #[test]
+    fn test_render_line_pattern_without_sprites_uses_line_color() {
+        let layer = make_layer("trail");
+        let p = paint(json!({"line-pattern": "dash", "line-color": "#ff0000"}));
+        let (svg, _, _) = render_line(&layer, &p, &config(false), &[], None).unwrap();
+        let svg = svg.to_string();
+        assert!(!svg.contains("<pattern"));
+        assert!(svg.contains(r##"stroke="#ff0000""##));
+    }
+
+    #[test]
+    fn test_render_line_gradient_requires_line_progress() {
+        let layer = make_layer("route");
+        let p = paint(json!({
+            "line-color": "#00ff00",
+            "line-gradient": ["interpolate", ["linear"], ["zoom"], 0, "#0000ff", 10, "#ff0000"]
+        }));
+        let (svg, _, _) = render_line(&layer, &p, &config(false), &[], None).unwrap();
+        let svg = svg.to_string();
+        assert!(!svg.contains("<linearGradient"));
+        assert!(svg.contains(r##"stroke="#00ff00""##));
+    }
+
+    #[test]
+    fn test_render_line_gradient() {
+        let layer = make_layer("route");
+        let p = paint(json!({
+            "line-gradient": [
+                "interpolate", ["linear"], ["line-progress"],
+                0, "#0000ff",
+                0.5, "#00ff00",
+                1, "#ff0000"
+            ]
+        }));
+        let (svg, _, height) = render_line(&layer, &p, &config(false), &[], None).unwrap();
+        let svg = svg.to_string();
+        assert!(svg.contains("<linearGradient"));
+        assert!(svg.contains("url(#route-gradient-0)"));
+        assert!(svg.contains(r#"offset="50%""#));
+        assert!(svg.contains("#ff0000"));
+        assert!(svg.contains("route"));
+        assert_eq!(height, 50);
+    }
+
+    #[test]
+    fn test_parse_gradient_stops_step() {
+        let layer = make_layer("route");
+        let expr = json!(["step", ["line-progress"], "#0000ff", 0.5, "#ff0000"]);
+        let stops = parse_gradient_stops(&layer, &expr).unwrap().unwrap();
+        assert_eq!(
+            stops,
+            vec![
+                (0.0, "#0000ff".to_string()),
+                (0.5, "#0000ff".to_string()),
+                (0.5, "#ff0000".to_string()),
+                (1.0, "#ff0000".to_string())
+            ]
+        );
     }
 }
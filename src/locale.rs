@@ -0,0 +1,163 @@
+use serde_json::{Map, Value};
+
+/// Words used in generated labels, in one language.
+pub struct Words {
+    /// Joins the conditions of `all`.
+    pub and: &'static str,
+    /// Joins the conditions of `any`.
+    pub or: &'static str,
+    /// Negates a condition: `not (...)`.
+    pub not: &'static str,
+    /// `has field`.
+    pub has: &'static str,
+    /// `without field`.
+    pub without: &'static str,
+    /// `field in a, b`.
+    pub is_in: &'static str,
+    /// `field not in a, b`.
+    pub not_in: &'static str,
+    /// `field contains x`.
+    pub contains: &'static str,
+    /// `field does not contain x`.
+    pub does_not_contain: &'static str,
+    /// Name of the `geometry-type` / `$type` input.
+    pub geometry_type: &'static str,
+    /// Heading of the graduated height section of `fill-extrusion` legends.
+    pub height: &'static str,
+    /// Title of [`crate::MapLibreLegend::render_all`] for assistive technologies, when
+    /// the legend has no title of its own.
+    pub legend: &'static str,
+    /// Separator of the decimals of numbers.
+    pub decimal_separator: char,
+    /// Separator of the thousands of numbers.
+    pub group_separator: char,
+}
+
+const EN: Words = Words {
+    and: "and",
+    or: "or",
+    not: "not",
+    has: "has",
+    without: "without",
+    is_in: "in",
+    not_in: "not in",
+    contains: "contains",
+    does_not_contain: "does not contain",
+    geometry_type: "geometry type",
+    height: "Height",
+    legend: "Legend",
+    decimal_separator: '.',
+    group_separator: ',',
+};
+
+const ES: Words = Words {
+    and: "y",
+    or: "o",
+    not: "no",
+    has: "tiene",
+    without: "sin",
+    is_in: "en",
+    not_in: "no en",
+    contains: "contiene",
+    does_not_contain: "no contiene",
+    geometry_type: "tipo de geometría",
+    height: "Altura",
+    legend: "Leyenda",
+    decimal_separator: ',',
+    group_separator: '.',
+};
+
+const PT: Words = Words {
+    and: "e",
+    or: "ou",
+    not: "não",
+    has: "tem",
+    without: "sem",
+    is_in: "em",
+    not_in: "não em",
+    contains: "contém",
+    does_not_contain: "não contém",
+    geometry_type: "tipo de geometria",
+    height: "Altura",
+    legend: "Legenda",
+    decimal_separator: ',',
+    group_separator: '.',
+};
+
+/// Returns the words for a locale such as `es` or `pt-BR`: Spanish, Portuguese, or
+/// English for any other locale and when none is set.
+pub fn words(locale: Option<&str>) -> &'static Words {
+    match locale.map(language) {
+        Some(lang) if lang.eq_ignore_ascii_case("es") => &ES,
+        Some(lang) if lang.eq_ignore_ascii_case("pt") => &PT,
+        _ => &EN,
+    }
+}
+
+/// Returns the language of a locale: `pt` for `pt-BR` or `pt_BR`.
+fn language(locale: &str) -> &str {
+    locale.split(['-', '_']).next().unwrap_or(locale)
+}
+
+/// Looks up `key` in a `metadata.legend` object, preferring its localized variants: for
+/// the `pt-BR` locale, `key:pt-BR`, then `key:pt`, then `key`.
+pub fn localized<'a>(
+    legend: &'a Map<String, Value>,
+    key: &str,
+    locale: Option<&str>,
+) -> Option<&'a Value> {
+    if let Some(locale) = locale {
+        for tag in [locale, language(locale)] {
+            if let Some(value) = legend.get(&format!("{}:{}", key, tag)) {
+                return Some(value);
+            }
+        }
+    }
+    legend.get(key)
+}
+
+/// Removes the localized variants (`key:...`) of `key`, so that the plain key applies in
+/// every locale.
+pub fn remove_localized(legend: &mut Map<String, Value>, key: &str) {
+    let prefix = format!("{}:", key);
+    legend.retain(|k, _| !k.starts_with(&prefix));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_localized_falls_back_to_language_then_key() {
+        let legend =
+            json!({"label": "Land use", "label:es": "Uso del suelo", "label:pt-BR": "Uso do solo"});
+        let legend = legend.as_object().unwrap();
+        let label = |locale| localized(legend, "label", locale).and_then(|v| v.as_str());
+        assert_eq!(label(None), Some("Land use"));
+        assert_eq!(label(Some("es")), Some("Uso del suelo"));
+        assert_eq!(label(Some("es-AR")), Some("Uso del suelo"));
+        assert_eq!(label(Some("pt-BR")), Some("Uso do solo"));
+        assert_eq!(label(Some("pt")), Some("Land use"));
+        assert_eq!(label(Some("fr")), Some("Land use"));
+    }
+
+    #[test]
+    fn test_words() {
+        assert_eq!(words(None).and, "and");
+        assert_eq!(words(Some("ES")).and, "y");
+        assert_eq!(words(Some("pt_PT")).without, "sem");
+        assert_eq!(words(Some("de")).has, "has");
+    }
+
+    #[test]
+    fn test_remove_localized() {
+        let mut legend = json!({"label": "a", "label:es": "b", "labels": "c"})
+            .as_object()
+            .unwrap()
+            .clone();
+        remove_localized(&mut legend, "label");
+        assert_eq!(legend.len(), 2);
+        assert!(legend.contains_key("labels"));
+    }
+}
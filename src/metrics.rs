@@ -0,0 +1,132 @@
+/// Advance widths of Helvetica (metric-compatible with Arial and Liberation Sans) for the
+/// printable ASCII range `' '..='~'`, in thousandths of the font size, from the Adobe
+/// core font metrics.
+#[rustfmt::skip]
+const REGULAR: [u16; 95] = [
+    // ' '..'/'
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278,
+    // '0'..'?'
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556,
+    // '@'..'O'
+    1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722, 778,
+    // 'P'..'_'
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556,
+    // '`'..'o'
+    333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556,
+    // 'p'..'~'
+    556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+/// Advance widths of Helvetica Bold, laid out as [`REGULAR`].
+#[rustfmt::skip]
+const BOLD: [u16; 95] = [
+    // ' '..'/'
+    278, 333, 474, 556, 556, 889, 722, 238, 333, 333, 389, 584, 278, 333, 278, 278,
+    // '0'..'?'
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 333, 333, 584, 584, 584, 611,
+    // '@'..'O'
+    975, 722, 722, 722, 722, 667, 611, 778, 722, 278, 556, 722, 611, 833, 722, 778,
+    // 'P'..'_'
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 333, 278, 333, 584, 556,
+    // '`'..'o'
+    333, 556, 611, 556, 611, 556, 333, 611, 611, 278, 278, 556, 278, 889, 611, 611,
+    // 'p'..'~'
+    611, 611, 389, 556, 333, 611, 556, 778, 556, 556, 500, 389, 280, 389, 584,
+];
+
+/// Returns the advance width of a character, in thousandths of the font size.
+///
+/// Characters outside printable ASCII use the width of their base letter when they are an
+/// accented Latin letter, a few common symbols have their own width, wide (CJK) characters
+/// take a full em, and anything else the width of an average lowercase letter.
+pub fn advance(c: char, is_bold: bool) -> u32 {
+    let table = if is_bold { &BOLD } else { &REGULAR };
+    let ascii = |c: char| table[c as usize - ' ' as usize] as u32;
+    match c {
+        ' '..='~' => ascii(c),
+        '\u{a0}' => ascii(' '),
+        '–' | '€' => 556,
+        '—' | '…' | '‰' => 1000,
+        '°' => 400,
+        '²' | '³' | '¹' => 333,
+        '·' | '•' => 350,
+        '≤' | '≥' | '≠' | '±' | '×' | '÷' => 584,
+        _ if is_wide(c) => 1000,
+        _ => match base_letter(c) {
+            Some(base) => ascii(base),
+            None if c.is_uppercase() => ascii('H'),
+            None => ascii('n'),
+        },
+    }
+}
+
+/// Returns the ASCII letter an accented Latin-1 or Latin Extended-A letter is drawn from.
+fn base_letter(c: char) -> Option<char> {
+    let base = match c {
+        'À'..='Å' | 'Ā' | 'Ă' | 'Ą' => 'A',
+        'à'..='å' | 'ā' | 'ă' | 'ą' => 'a',
+        'Æ' => 'W',
+        'æ' => 'm',
+        'Ç' | 'Ć' | 'Č' => 'C',
+        'ç' | 'ć' | 'č' => 'c',
+        'È'..='Ë' | 'Ē' | 'Ę' | 'Ě' => 'E',
+        'è'..='ë' | 'ē' | 'ę' | 'ě' => 'e',
+        'Ì'..='Ï' | 'Ī' | 'İ' => 'I',
+        'ì'..='ï' | 'ī' | 'ı' => 'i',
+        'Ñ' | 'Ń' | 'Ň' => 'N',
+        'ñ' | 'ń' | 'ň' => 'n',
+        'Ò'..='Ö' | 'Ø' | 'Ō' | 'Ő' => 'O',
+        'ò'..='ö' | 'ø' | 'ō' | 'ő' => 'o',
+        'Ù'..='Ü' | 'Ū' | 'Ů' | 'Ű' => 'U',
+        'ù'..='ü' | 'ū' | 'ů' | 'ű' => 'u',
+        'Ý' | 'Ÿ' => 'Y',
+        'ý' | 'ÿ' => 'y',
+        'Ł' => 'L',
+        'ł' => 'l',
+        'Ś' | 'Š' => 'S',
+        'ś' | 'š' | 'ß' => 's',
+        'Ź' | 'Ż' | 'Ž' => 'Z',
+        'ź' | 'ż' | 'ž' => 'z',
+        _ => return None,
+    };
+    Some(base)
+}
+
+/// Whether a character is drawn a full em wide: CJK ideographs, kana, Hangul and
+/// full-width forms.
+fn is_wide(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x1100..=0x115F | 0x2E80..=0xA4CF | 0xAC00..=0xD7A3 | 0xF900..=0xFAFF | 0xFF00..=0xFF60
+    )
+}
+
+/// Measures `text` set at `font_size` pixels, rounded up to whole pixels.
+pub fn measure(text: &str, font_size: u32, is_bold: bool) -> u32 {
+    let units: u32 = text.chars().map(|c| advance(c, is_bold)).sum();
+    (units * font_size).div_ceil(1000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_measure_ascii() {
+        // H(722) + i(222) = 944 → 13.2 px at 14 px
+        assert_eq!(measure("Hi", 14, false), 14);
+        // Bold: H(722) + i(278) = 1000 → 14 px
+        assert_eq!(measure("Hi", 14, true), 14);
+        assert_eq!(measure("", 14, false), 0);
+        // Narrow letters measure narrower than wide ones of the same count.
+        assert!(measure("iiii", 14, false) < measure("WWWW", 14, false));
+    }
+
+    #[test]
+    fn test_measure_non_ascii() {
+        assert_eq!(measure("é", 10, false), measure("e", 10, false));
+        assert_eq!(measure("Ñ", 10, true), measure("N", 10, true));
+        assert_eq!(measure("公园", 10, false), 20);
+        assert_eq!(measure("–", 1000, false), 556);
+    }
+}
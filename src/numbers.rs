@@ -0,0 +1,280 @@
+use crate::{
+    LegendConfig,
+    common::{Layer, get_legend_object, legend_str},
+    error::LegendError,
+    locale::words,
+};
+use serde_json::Value;
+
+/// Largest number of `decimals` written; larger values are clamped to it.
+const MAX_DECIMALS: u64 = 20;
+
+/// How the thresholds and stops of `step` and `interpolate` labels are written, from the
+/// layer's `metadata.legend` options:
+///
+/// - `decimals`: fixed number of decimals, at most 20 (by default, as many as the value
+///   needs);
+/// - `unit`: suffix after the value, e.g. `m²`;
+/// - `grouping`: whether to separate thousands;
+/// - `range-template`: label of a range between two values, with `{min}`, `{max}`,
+///   `{unit}` and `{field}` placeholders, e.g. `"{min} – {max} {unit}"`;
+/// - `interpolate-labels`: how `interpolate` stops are labelled (see [`StopLabels`]).
+///
+/// Decimal and thousands separators follow [`LegendConfig::locale`].
+pub struct NumberFormat {
+    decimals: Option<usize>,
+    unit: Option<String>,
+    grouping: bool,
+    range_template: Option<String>,
+    stop_labels: StopLabels,
+    decimal_separator: char,
+    group_separator: char,
+}
+
+impl NumberFormat {
+    /// Reads the number format options of a layer.
+    pub fn for_layer(layer: &Layer, config: &LegendConfig) -> Result<Self, LegendError> {
+        let legend = get_legend_object(layer)?;
+        let option = |key: &str| legend.and_then(|l| l.get(key));
+        let words = words(config.locale.as_deref());
+        Ok(Self {
+            decimals: option("decimals")
+                .and_then(|d| d.as_u64())
+                .map(|d| d.min(MAX_DECIMALS) as usize),
+            unit: legend_str(layer, config, "unit")?.map(|u| u.to_string()),
+            grouping: option("grouping").and_then(|g| g.as_bool()) == Some(true),
+            range_template: legend_str(layer, config, "range-template")?.map(|t| t.to_string()),
+            stop_labels: match option("interpolate-labels") {
+                None => StopLabels::AtLeast,
+                Some(value) => StopLabels::parse(value)?,
+            },
+            decimal_separator: words.decimal_separator,
+            group_separator: words.group_separator,
+        })
+    }
+
+    /// Writes a number with the configured decimals and separators, without the unit.
+    pub fn number(&self, value: f64) -> String {
+        let text = match self.decimals {
+            Some(decimals) => format!("{:.*}", decimals, value),
+            None => value.to_string(),
+        };
+        let (sign, text) = match text.strip_prefix('-') {
+            Some(rest) => ("-", rest),
+            None => ("", text.as_str()),
+        };
+        let (integer, fraction) = match text.split_once('.') {
+            Some((integer, fraction)) => (integer, Some(fraction)),
+            None => (text, None),
+        };
+        let mut result = sign.to_string();
+        for (i, c) in integer.chars().enumerate() {
+            if self.grouping && i > 0 && (integer.len() - i).is_multiple_of(3) {
+                result.push(self.group_separator);
+            }
+            result.push(c);
+        }
+        if let Some(fraction) = fraction {
+            result.push(self.decimal_separator);
+            result.push_str(fraction);
+        }
+        result
+    }
+
+    /// Writes a number followed by the unit, if any.
+    pub fn value(&self, value: f64) -> String {
+        self.with_unit(self.number(value))
+    }
+
+    /// Appends the unit, if any, to a label.
+    fn with_unit(&self, label: String) -> String {
+        match &self.unit {
+            Some(unit) => format!("{} {}", label, unit),
+            None => label,
+        }
+    }
+
+    /// Label of the values of `field` below `max`: `field < max unit`.
+    pub fn below(&self, field: &str, max: f64) -> String {
+        self.with_unit(format!("{} < {}", field, self.number(max)))
+    }
+
+    /// Label of the values of `field` above `min`: `field > min unit`.
+    pub fn above(&self, field: &str, min: f64) -> String {
+        self.with_unit(format!("{} > {}", field, self.number(min)))
+    }
+
+    /// Label of the values of `field` from `min` up: `field ≥ min unit`.
+    pub fn at_least(&self, field: &str, min: f64) -> String {
+        self.with_unit(format!("{} ≥ {}", field, self.number(min)))
+    }
+
+    /// Label of the values of `field` from `min` up to `max`: the `range-template`, or
+    /// `min ≤ field < max unit`.
+    pub fn range(&self, field: &str, min: f64, max: f64) -> String {
+        match &self.range_template {
+            Some(template) => template
+                .replace("{min}", &self.number(min))
+                .replace("{max}", &self.number(max))
+                .replace("{unit}", self.unit.as_deref().unwrap_or_default())
+                .replace("{field}", field)
+                .trim()
+                .to_string(),
+            None => self.with_unit(format!(
+                "{} ≤ {} < {}",
+                self.number(min),
+                field,
+                self.number(max)
+            )),
+        }
+    }
+
+    /// Label of the `index`-th of the `stops` of an `interpolate` expression over `field`,
+    /// following the layer's `interpolate-labels` option.
+    pub fn stop(&self, field: &str, stops: &[f64], index: usize) -> String {
+        let value = stops[index];
+        let last = index + 1 == stops.len();
+        match self.stop_labels {
+            StopLabels::AtLeast => self.at_least(field, value),
+            StopLabels::Stops => self.value(value),
+            StopLabels::Ranges if last => self.at_least(field, value),
+            StopLabels::Ranges => self.range(field, value, stops[index + 1]),
+            StopLabels::Bounds if index == 0 && !last => self.below(field, value),
+            StopLabels::Bounds if last && index > 0 => self.above(field, value),
+            StopLabels::Bounds => self.value(value),
+        }
+    }
+}
+
+/// How the stops of `interpolate` expressions are labelled, set with the
+/// `interpolate-labels` option of `metadata.legend`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StopLabels {
+    /// `"at-least"`: `field ≥ stop` (default).
+    #[default]
+    AtLeast,
+    /// `"stops"`: the stop value itself, e.g. `1,000 m`.
+    Stops,
+    /// `"ranges"`: the range from each stop to the next, written like the ranges of `step`
+    /// labels (`a ≤ field < b`, or the `range-template`), and `field ≥ z` for the last.
+    Ranges,
+    /// `"bounds"`: `field < a` for the first stop, `field > z` for the last, and the
+    /// values of the stops in between.
+    Bounds,
+}
+
+impl StopLabels {
+    fn parse(value: &Value) -> Result<Self, LegendError> {
+        match value.as_str() {
+            Some("at-least") => Ok(Self::AtLeast),
+            Some("stops") => Ok(Self::Stops),
+            Some("ranges") => Ok(Self::Ranges),
+            Some("bounds") => Ok(Self::Bounds),
+            _ => Err(LegendError::InvalidJson(format!(
+                "'interpolate-labels' must be \"at-least\", \"stops\", \"ranges\" or \"bounds\", got {}",
+                value
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn format(legend: serde_json::Value, locale: Option<&str>) -> NumberFormat {
+        let layer: Layer = serde_json::from_value(json!({
+            "id": "l", "type": "fill", "metadata": {"legend": legend}
+        }))
+        .unwrap();
+        let config = LegendConfig {
+            locale: locale.map(|l| l.to_string()),
+            ..Default::default()
+        };
+        NumberFormat::for_layer(&layer, &config).unwrap()
+    }
+
+    #[test]
+    fn test_number_defaults_match_plain_values() {
+        let format = format(json!({}), None);
+        assert_eq!(format.number(100.5), "100.5");
+        assert_eq!(format.number(1000000.0), "1000000");
+        assert_eq!(format.range("precio", 0.0, 100.5), "0 ≤ precio < 100.5");
+    }
+
+    #[test]
+    fn test_number_decimals_grouping_and_locale() {
+        let en = format(json!({"decimals": 2, "grouping": true}), None);
+        assert_eq!(en.number(1234567.891), "1,234,567.89");
+        assert_eq!(en.number(-1234.0), "-1,234.00");
+        assert_eq!(en.number(999.0), "999.00");
+        let es = format(json!({"decimals": 1, "grouping": true}), Some("es-AR"));
+        assert_eq!(es.number(1234.56), "1.234,6");
+        let pt = format(json!({}), Some("pt"));
+        assert_eq!(pt.number(0.25), "0,25");
+    }
+
+    #[test]
+    fn test_decimals_are_clamped() {
+        let format = format(json!({"decimals": 100000}), None);
+        assert_eq!(format.number(1.5), format!("1.5{}", "0".repeat(19)));
+    }
+
+    #[test]
+    fn test_unit_and_range_template() {
+        let format = format(
+            json!({"unit": "USD/m²", "grouping": true, "range-template": "{min} – {max} {unit}"}),
+            None,
+        );
+        assert_eq!(
+            format.range("precio", 1000.0, 2500.0),
+            "1,000 – 2,500 USD/m²"
+        );
+        assert_eq!(format.below("precio", 1000.0), "precio < 1,000 USD/m²");
+        assert_eq!(format.at_least("precio", 2500.0), "precio ≥ 2,500 USD/m²");
+        let without_unit = NumberFormat {
+            unit: None,
+            ..format
+        };
+        assert_eq!(without_unit.range("precio", 1.0, 2.0), "1 – 2");
+    }
+
+    #[test]
+    fn test_stop_labels() {
+        let stops = [0.0, 1000.0, 5000.0];
+        let labels = |legend| {
+            let format = format(legend, None);
+            (0..stops.len())
+                .map(|i| format.stop("pop", &stops, i))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(labels(json!({})), ["pop ≥ 0", "pop ≥ 1000", "pop ≥ 5000"]);
+        assert_eq!(
+            labels(json!({"interpolate-labels": "stops", "grouping": true, "unit": "hab"})),
+            ["0 hab", "1,000 hab", "5,000 hab"]
+        );
+        assert_eq!(
+            labels(json!({"interpolate-labels": "ranges"})),
+            ["0 ≤ pop < 1000", "1000 ≤ pop < 5000", "pop ≥ 5000"]
+        );
+        assert_eq!(
+            labels(json!({"interpolate-labels": "ranges", "range-template": "{min} – {max}"})),
+            ["0 – 1000", "1000 – 5000", "pop ≥ 5000"]
+        );
+        assert_eq!(
+            labels(json!({"interpolate-labels": "bounds"})),
+            ["pop < 0", "1000", "pop > 5000"]
+        );
+    }
+
+    #[test]
+    fn test_invalid_stop_labels() {
+        let layer: Layer = serde_json::from_value(json!({
+            "id": "l", "type": "fill",
+            "metadata": {"legend": {"interpolate-labels": "steps"}}
+        }))
+        .unwrap();
+        assert!(NumberFormat::for_layer(&layer, &LegendConfig::default()).is_err());
+    }
+}
@@ -1,6 +1,8 @@
 use crate::{
+    LegendConfig,
     common::{Layer, render_label},
     error::LegendError,
+    layout::inline_width,
 };
 use svg::Document;
 use svg::node::element::Rectangle;
@@ -10,15 +12,20 @@ use svg::node::element::Rectangle;
 /// Shows a 4×2 checkerboard of muted colors as a generic raster placeholder.
 /// The actual raster colors are not read from the style.
 ///
-/// Returns `(svg_string, width, height)`.
+/// Returns `(document, width, height)`.
 pub fn render_raster(
     layer: &Layer,
-    default_width: u32,
-    default_height: u32,
-    has_label: bool,
-) -> Result<(String, u32, u32), LegendError> {
-    let total_w = 30.0;
-    let total_h = 20.0;
+    config: &LegendConfig,
+) -> Result<(Document, u32, u32), LegendError> {
+    let &LegendConfig {
+        default_height,
+        has_label,
+        ref theme,
+        ..
+    } = config;
+    let width = inline_width(layer, config, theme.inline_label_x())?;
+    let total_w = theme.swatch_width as f32;
+    let total_h = theme.swatch_height as f32;
     let cols = 4;
     let rows = 2;
 
@@ -26,12 +33,12 @@ pub fn render_raster(
     let cell_h = total_h / rows as f32;
 
     let mut doc = Document::new()
-        .set("width", default_width)
+        .set("width", width)
         .set("height", default_height)
         .add(
             Rectangle::new()
-                .set("x", 10)
-                .set("y", 10)
+                .set("x", theme.padding)
+                .set("y", theme.padding)
                 .set("width", total_w)
                 .set("height", total_h)
                 .set("fill", "#f8f9fa"),
@@ -49,8 +56,8 @@ pub fn render_raster(
 
             doc = doc.add(
                 Rectangle::new()
-                    .set("x", 10.0 + x)
-                    .set("y", 10.0 + y)
+                    .set("x", theme.padding as f32 + x)
+                    .set("y", theme.padding as f32 + y)
                     .set("width", cell_w)
                     .set("height", cell_h)
                     .set("fill", color)
@@ -62,8 +69,8 @@ pub fn render_raster(
 
     doc = doc.add(
         Rectangle::new()
-            .set("x", 10)
-            .set("y", 10)
+            .set("x", theme.padding)
+            .set("y", theme.padding)
             .set("width", total_w - 0.0)
             .set("height", total_h - 0.0)
             .set("fill", "none")
@@ -72,8 +79,8 @@ pub fn render_raster(
     );
 
     if has_label {
-        render_label(layer, &mut doc, None, None, None)?;
+        render_label(layer, config, &mut doc, None, None, None)?;
     }
 
-    Ok((doc.to_string(), default_width, default_height))
+    Ok((doc, width, default_height))
 }
@@ -1,6 +1,8 @@
 use crate::{
-    common::{Layer, get_icon_data_url, parse_expression, render_label, render_separator},
+    LegendConfig,
+    common::{Layer, get_icon_data_url, parse_expression, render_label},
     error::LegendError,
+    layout::{RowLayout, inline_width},
 };
 use image::DynamicImage;
 use serde_json::Value;
@@ -23,25 +25,34 @@ pub fn get_layout_object(layer: &Layer) -> Result<&serde_json::Map<String, Value
 ///
 /// Priority: `icon-image` is rendered first (as a sprite icon), then `text-field` (as a bold "T").
 /// - String `icon-image`: renders the named sprite icon; requires `sprite_data` to be loaded.
-/// - Array `icon-image`: expression-based, renders one icon per case.
+/// - Array `icon-image`: expression-based, renders one icon per case (or per entry of
+///   `entries`, when given).
 /// - `text-field` only: renders a bold "T" placeholder.
 ///
 /// Returns [`LegendError::InvalidJson`] if neither `icon-image` nor `text-field` is present,
 /// or if sprites are required but not loaded.
 ///
-/// Returns `(svg_string, width, height)`.
+/// Returns `(document, width, height)`.
 pub fn render_symbol(
     layer: &Layer,
-    default_width: u32,
-    default_height: u32,
-    has_label: bool,
+    config: &LegendConfig,
     sprite_data: &[(DynamicImage, Value)],
-) -> Result<(String, u32, u32), LegendError> {
+    entries: Option<&[(String, String)]>,
+) -> Result<(Document, u32, u32), LegendError> {
+    let &LegendConfig {
+        default_height,
+        has_label,
+        ref theme,
+        ..
+    } = config;
+    let icon_size = theme.swatch_height;
+    let baseline = theme.baseline(theme.padding as i32) as u32;
     let layout = get_layout_object(layer)?;
     let text_field = layout.get("text-field");
     let icon_image = layout.get("icon-image");
 
-    let mut doc = Document::new().set("width", default_width);
+    let mut doc = Document::new();
+    let width;
     let mut height = default_height;
 
     if let Some(icon_image) = icon_image {
@@ -54,44 +65,46 @@ pub fn render_symbol(
         if let Some(icon_name) = icon_image.as_str() {
             let data_url = get_icon_data_url(sprite_data, icon_name)?;
             let image = Image::new()
-                .set("x", 10)
-                .set("y", 10)
-                .set("width", 20)
-                .set("height", 20)
+                .set("x", theme.padding)
+                .set("y", theme.padding)
+                .set("width", icon_size)
+                .set("height", icon_size)
                 .set("href", data_url);
             doc = doc.add(image);
 
+            let label_x = theme.icon_label_x();
             if has_label {
-                render_label(layer, &mut doc, Some(40), Some(25), Some(false))?;
+                render_label(layer, config, &mut doc, Some(label_x), Some(baseline), None)?;
             }
+            width = inline_width(layer, config, label_x)?;
         } else if let Some(_arr) = icon_image.as_array() {
-            let cases = parse_expression(layer, icon_image)?;
+            let cases = match entries {
+                Some(entries) => entries.to_vec(),
+                None => parse_expression(layer, config, icon_image)?,
+            };
+            let rows = RowLayout::new(layer, config, &cases, theme.icon_label_x())?;
+            let init_y = if rows.title_row {
+                theme.padding + theme.title_height()
+            } else {
+                theme.padding
+            } as i32;
             if has_label {
-                render_label(layer, &mut doc, Some(10), Some(20), Some(true))?;
-                render_separator(&mut doc, default_width, 0, 10);
+                rows.render_title(layer, config, &mut doc, theme.baseline(init_y))?;
             }
-            let mut y = if has_label { 40 } else { 10 };
-            for (label, icon_name) in cases {
+            for (i, (_, icon_name)) in cases.into_iter().enumerate() {
+                let (dx, y) = rows.position(i, init_y);
                 let data_url = get_icon_data_url(sprite_data, &icon_name)?;
                 let image = Image::new()
-                    .set("x", 10)
+                    .set("x", theme.padding + dx)
                     .set("y", y)
-                    .set("width", 20)
-                    .set("height", 20)
+                    .set("width", icon_size)
+                    .set("height", icon_size)
                     .set("href", data_url);
-                doc = doc.add(image);
-
-                let text = SvgText::new("")
-                    .set("x", 40)
-                    .set("y", y + 15)
-                    .set("font-size", 14)
-                    .set("fill", "black")
-                    .add(svg::node::Text::new(label));
-                doc = doc.add(text);
-
-                y += 30;
+                let label_x = theme.icon_label_x() + dx;
+                rows.render_entry(theme, &mut doc, i, image, label_x, theme.baseline(y));
             }
-            height = y + 10;
+            width = rows.width;
+            height = init_y as u32 + rows.rows_height + theme.padding;
             doc = doc.set("height", height);
         } else {
             return Err(LegendError::InvalidJson(
@@ -99,17 +112,22 @@ pub fn render_symbol(
             ));
         }
     } else if text_field.is_some() {
-        let t_text = SvgText::new("T")
-            .set("x", 14)
-            .set("y", 25)
-            .set("font-size", 16)
+        let mut t_text = SvgText::new("T")
+            .set("x", theme.padding + 4)
+            .set("y", baseline)
+            .set("font-size", theme.font_size + 2)
             .set("font-weight", "bold")
-            .set("fill", "black");
+            .set("fill", theme.text_color.as_str());
+        if let Some(family) = &theme.font_family {
+            t_text = t_text.set("font-family", family.as_str());
+        }
         doc = doc.add(t_text);
 
+        let label_x = theme.padding + icon_size;
         if has_label {
-            render_label(layer, &mut doc, Some(30), Some(25), Some(false))?;
+            render_label(layer, config, &mut doc, Some(label_x), Some(baseline), None)?;
         }
+        width = inline_width(layer, config, label_x)?;
         doc = doc.set("height", default_height);
     } else {
         return Err(LegendError::InvalidJson(
@@ -117,7 +135,8 @@ pub fn render_symbol(
         ));
     }
 
-    Ok((doc.to_string(), default_width, height))
+    doc = doc.set("width", width);
+    Ok((doc, width, height))
 }
 
 #[cfg(test)]
@@ -126,6 +145,13 @@ mod tests {
     use crate::common::Layer;
     use serde_json::json;
 
+    fn config(has_label: bool) -> LegendConfig {
+        LegendConfig {
+            has_label,
+            ..Default::default()
+        }
+    }
+
     fn make_layer_with_layout(id: &str, layout: serde_json::Value) -> Layer {
         serde_json::from_value(json!({"id": id, "type": "symbol", "layout": layout})).unwrap()
     }
@@ -133,7 +159,8 @@ mod tests {
     #[test]
     fn test_render_symbol_text_field() {
         let layer = make_layer_with_layout("sym", json!({"text-field": "{name}"}));
-        let (svg, width, height) = render_symbol(&layer, 200, 40, false, &[]).unwrap();
+        let (svg, width, height) = render_symbol(&layer, &config(false), &[], None).unwrap();
+        let svg = svg.to_string();
         assert_eq!(width, 200);
         assert_eq!(height, 40);
         // Should render a bold "T" placeholder for text-only symbols
@@ -143,19 +170,19 @@ mod tests {
     #[test]
     fn test_render_symbol_missing_layout_returns_err() {
         let layer: Layer = serde_json::from_value(json!({"id": "sym", "type": "symbol"})).unwrap();
-        assert!(render_symbol(&layer, 200, 40, false, &[]).is_err());
+        assert!(render_symbol(&layer, &config(false), &[], None).is_err());
     }
 
     #[test]
     fn test_render_symbol_icon_without_sprite_returns_err() {
         let layer = make_layer_with_layout("sym", json!({"icon-image": "marker"}));
         // icon-image requires sprite data; empty slice → error
-        assert!(render_symbol(&layer, 200, 40, false, &[]).is_err());
+        assert!(render_symbol(&layer, &config(false), &[], None).is_err());
     }
 
     #[test]
     fn test_render_symbol_neither_text_nor_icon_returns_err() {
         let layer = make_layer_with_layout("sym", json!({"visibility": "visible"}));
-        assert!(render_symbol(&layer, 200, 40, false, &[]).is_err());
+        assert!(render_symbol(&layer, &config(false), &[], None).is_err());
     }
 }
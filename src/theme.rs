@@ -0,0 +1,192 @@
+use svg::node::element::Rectangle;
+use svg::{Document, Node};
+
+/// Visual styling of the rendered legends: fonts, colors and spacing.
+///
+/// Set through [`crate::LegendConfig::theme`]. Every field is public; use [`Default`] and
+/// override what you need:
+///
+/// ```rust,ignore
+/// let theme = LegendTheme {
+///     font_family: Some("Inter, sans-serif".to_string()),
+///     text_color: "#1a1a1a".to_string(),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct LegendTheme {
+    /// CSS font family of all legend text. `None` leaves it to the SVG viewer.
+    /// Default: `None`.
+    pub font_family: Option<String>,
+    /// Font size in pixels of entry labels. Default: `14`.
+    pub font_size: u32,
+    /// Font size in pixels of layer titles and group headings. Default: `14`.
+    pub title_font_size: u32,
+    /// Font size in pixels of the overall title of [`crate::MapLibreLegend::render_all`].
+    /// Default: `18`.
+    pub legend_title_font_size: u32,
+    /// Font weight of entry labels. Default: `"normal"`.
+    pub font_weight: String,
+    /// Font weight of layer titles and group headings. Default: `"bold"`.
+    pub title_font_weight: String,
+    /// Color of all legend text. Default: `"black"`.
+    pub text_color: String,
+    /// Width in pixels of a swatch (rectangle, sample line, block). Default: `30`.
+    pub swatch_width: u32,
+    /// Height in pixels of a swatch; circles and icons are this size across. Default: `20`.
+    pub swatch_height: u32,
+    /// Height in pixels of each entry row. Default: `30`.
+    pub row_height: u32,
+    /// Margin in pixels around the swatches and labels. Default: `10`.
+    pub padding: u32,
+    /// Horizontal space in pixels between entries in horizontal layouts. Default: `15`.
+    pub entry_gap: u32,
+    /// Color of the line below layer titles and group headings. Default: `"#999999"`.
+    pub separator_color: String,
+    /// Width in pixels of the line below titles. Default: `0.5`.
+    pub separator_width: f64,
+    /// Color of the divider between sections of
+    /// [`crate::MapLibreLegend::render_all`]. Default: `"#333333"`.
+    pub divider_color: String,
+    /// Width in pixels of the divider between sections. Default: `0.5`.
+    pub divider_width: f64,
+    /// Fill of the whole legend, behind everything else. `None` keeps it transparent.
+    /// Default: `None`.
+    pub background: Option<String>,
+}
+
+impl Default for LegendTheme {
+    fn default() -> Self {
+        Self {
+            font_family: None,
+            font_size: 14,
+            title_font_size: 14,
+            legend_title_font_size: 18,
+            font_weight: "normal".to_string(),
+            title_font_weight: "bold".to_string(),
+            text_color: "black".to_string(),
+            swatch_width: 30,
+            swatch_height: 20,
+            row_height: 30,
+            padding: 10,
+            entry_gap: 15,
+            separator_color: "#999999".to_string(),
+            separator_width: 0.5,
+            divider_color: "#333333".to_string(),
+            divider_width: 0.5,
+            background: None,
+        }
+    }
+}
+
+impl LegendTheme {
+    /// Distance from the vertical center of a swatch down to the baseline of its label,
+    /// which centers lowercase text on the swatch.
+    pub fn text_offset(&self) -> i32 {
+        (self.font_size as f64 * 0.35).round() as i32
+    }
+
+    /// Baseline of the label of a swatch whose top edge is at `top`.
+    pub fn baseline(&self, top: i32) -> i32 {
+        top + (self.swatch_height / 2) as i32 + self.text_offset()
+    }
+
+    /// X of entry labels drawn after a swatch.
+    pub fn label_x(&self) -> u32 {
+        self.padding + self.swatch_width + 5
+    }
+
+    /// X of the label of single-swatch legends (`background`, `heatmap`, ...).
+    pub fn inline_label_x(&self) -> u32 {
+        self.label_x() + 10
+    }
+
+    /// X of labels drawn after a square icon (circles, sprite icons).
+    pub fn icon_label_x(&self) -> u32 {
+        self.padding + self.swatch_height + 10
+    }
+
+    /// Baseline of a title drawn on a row of its own.
+    pub fn title_baseline(&self) -> u32 {
+        self.padding + (self.title_font_size as f64 * 0.72).round() as u32
+    }
+
+    /// Y of the separator below a title row.
+    pub fn separator_y(&self) -> u32 {
+        self.title_baseline() + 6
+    }
+
+    /// Height of a title row, separator included.
+    pub fn title_height(&self) -> u32 {
+        self.row_height.max(self.separator_y() + 4)
+    }
+
+    /// Height a title row adds to a legend: the title row, less the space the entry rows
+    /// already leave below the last swatch (none when swatches are taller than rows).
+    pub fn title_space(&self) -> u32 {
+        self.title_height()
+            .saturating_sub(self.row_height.saturating_sub(self.swatch_height))
+    }
+}
+
+/// Puts the theme background, if any, behind the content of a legend document.
+pub fn with_background(mut doc: Document, theme: &LegendTheme) -> Document {
+    if let (Some(fill), Some(children)) = (&theme.background, doc.get_children_mut()) {
+        let rect = Rectangle::new()
+            .set("width", "100%")
+            .set("height", "100%")
+            .set("fill", fill.as_str());
+        children.insert(0, Box::new(rect));
+    }
+    doc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_metrics_match_legacy_layout() {
+        let theme = LegendTheme::default();
+        assert_eq!(theme.baseline(10), 25);
+        assert_eq!(theme.label_x(), 45);
+        assert_eq!(theme.inline_label_x(), 55);
+        assert_eq!(theme.icon_label_x(), 40);
+        assert_eq!(theme.title_baseline(), 20);
+        assert_eq!(theme.separator_y(), 26);
+        assert_eq!(theme.title_height(), 30);
+        assert_eq!(theme.title_space(), 20);
+    }
+
+    #[test]
+    fn test_larger_title_font_grows_title_row() {
+        let theme = LegendTheme {
+            title_font_size: 24,
+            ..Default::default()
+        };
+        assert_eq!(theme.title_baseline(), 27);
+        assert_eq!(theme.title_height(), 37);
+    }
+
+    #[test]
+    fn test_swatch_taller_than_row() {
+        let theme = LegendTheme {
+            swatch_height: 40,
+            ..Default::default()
+        };
+        assert_eq!(theme.title_space(), theme.title_height());
+    }
+
+    #[test]
+    fn test_with_background() {
+        let doc = Document::new().add(Rectangle::new().set("fill", "#ff0000"));
+        let plain = with_background(doc.clone(), &LegendTheme::default()).to_string();
+        assert!(!plain.contains("100%"));
+        let theme = LegendTheme {
+            background: Some("#fafafa".to_string()),
+            ..Default::default()
+        };
+        let svg = with_background(doc, &theme).to_string();
+        assert!(svg.find("#fafafa").unwrap() < svg.find("#ff0000").unwrap());
+    }
+}